@@ -0,0 +1,91 @@
+//! Checks that every segment in `static/segments.json` can be cleared at the
+//! speeds `static/difficulty.json` uses it at.
+//!
+//! ```sh
+//! cargo run --bin validate_segments -- [--static-dir static] [--min-window 2]
+//! ```
+
+use anyhow::{anyhow, Context, Result};
+use rust_webpack_template::{png_size, validate_segments};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const DEFAULT_MIN_WINDOW: u32 = 2;
+
+struct Options {
+    static_dir: PathBuf,
+    min_window: u32,
+}
+
+fn parse_options() -> Result<Options> {
+    let mut options = Options {
+        static_dir: PathBuf::from("static"),
+        min_window: DEFAULT_MIN_WINDOW,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--static-dir" => {
+                options.static_dir = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--static-dir needs a path"))?
+                    .into()
+            }
+            "--min-window" => {
+                options.min_window = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--min-window needs a number of frames"))?
+                    .parse()
+                    .context("--min-window needs a number of frames")?
+            }
+            other => return Err(anyhow!("Unknown argument {}", other)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn read(static_dir: &Path, file_name: &str) -> Result<String> {
+    let path = static_dir.join(file_name);
+    fs::read_to_string(&path).with_context(|| format!("Could not read {}", path.display()))
+}
+
+fn run() -> Result<bool> {
+    let options = parse_options()?;
+    let reports = validate_segments(
+        &read(&options.static_dir, "segments.json")?,
+        &read(&options.static_dir, "difficulty.json")?,
+        &read(&options.static_dir, "rhb.json")?,
+        &read(&options.static_dir, "enemies.json")?,
+        |image| {
+            let path = options.static_dir.join(image);
+            png_size(
+                &fs::read(&path).with_context(|| format!("Could not read {}", path.display()))?,
+            )
+        },
+    )?;
+
+    let mut all_ok = true;
+    for report in &reports {
+        if report.is_ok(options.min_window) {
+            println!("ok      {}", report);
+        } else {
+            all_ok = false;
+            println!("FAILED  {}", report);
+        }
+    }
+
+    Ok(all_ok)
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{:#}", err);
+            ExitCode::from(2)
+        }
+    }
+}
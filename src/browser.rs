@@ -1,5 +1,6 @@
 use anyhow::{anyhow, Result};
 use js_sys::ArrayBuffer;
+use std::collections::HashMap;
 use std::future::Future;
 use wasm_bindgen::closure::{Closure, WasmClosure, WasmClosureFnOnce};
 use wasm_bindgen::{JsCast, JsValue};
@@ -155,6 +156,68 @@ pub fn hide_ui() -> Result<()> {
     }
 }
 
+pub trait Storage {
+    fn get_item(&self, key: &str) -> Result<Option<String>>;
+    fn set_item(&mut self, key: &str, value: &str) -> Result<()>;
+}
+
+pub struct LocalStorage {
+    storage: web_sys::Storage,
+}
+
+impl LocalStorage {
+    pub fn new() -> Result<Self> {
+        let storage = window()?
+            .local_storage()
+            .map_err(|err| anyhow!("Could not access localStorage {:#?}", err))?
+            .ok_or_else(|| anyhow!("No localStorage Found"))?;
+
+        Ok(LocalStorage { storage })
+    }
+}
+
+impl Storage for LocalStorage {
+    fn get_item(&self, key: &str) -> Result<Option<String>> {
+        self.storage
+            .get_item(key)
+            .map_err(|err| anyhow!("Could not read {} from localStorage {:#?}", key, err))
+    }
+
+    fn set_item(&mut self, key: &str, value: &str) -> Result<()> {
+        self.storage
+            .set_item(key, value)
+            .map_err(|err| anyhow!("Could not write {} to localStorage {:#?}", key, err))
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    items: HashMap<String, String>,
+}
+
+impl Storage for MemoryStorage {
+    fn get_item(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.items.get(key).cloned())
+    }
+
+    fn set_item(&mut self, key: &str, value: &str) -> Result<()> {
+        self.items.insert(key.into(), value.into());
+        Ok(())
+    }
+}
+
+/// Returns `window.localStorage` when the browser allows it (private browsing
+/// may not), falling back to storage that only lasts for this page load.
+pub fn storage() -> Box<dyn Storage> {
+    match LocalStorage::new() {
+        Ok(local_storage) => Box::new(local_storage),
+        Err(err) => {
+            error!("Falling back to in-memory storage {:#?}", err);
+            Box::new(MemoryStorage::default())
+        }
+    }
+}
+
 fn find_ui() -> Result<HtmlElement> {
     find_html_element_by_id("ui")
 }
@@ -173,6 +236,8 @@ pub fn find_html_element_by_id(id: &str) -> Result<HtmlElement> {
 }
 
 #[cfg(test)]
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
     use super::*;
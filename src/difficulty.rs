@@ -0,0 +1,159 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct Difficulty {
+    /// How many hits the boy can take in a run. Leave it out for the
+    /// original one-hit game.
+    #[serde(default = "one_life")]
+    lives: u8,
+    stages: Vec<Stage>,
+}
+
+fn one_life() -> u8 {
+    1
+}
+
+#[derive(Deserialize)]
+pub struct Stage {
+    pub from_meters: u32,
+    pub running_speed: i16,
+    pub segment_gap: i16,
+    pub segments: Vec<String>,
+}
+
+impl Difficulty {
+    /// Checks the invariants `stage_at` relies on, so a bad data file fails
+    /// at startup instead of in the middle of a run.
+    pub fn validate(self) -> Result<Self> {
+        if self.lives == 0 {
+            return Err(anyhow!("Difficulty must give the boy at least one life"));
+        }
+        match self.stages.first() {
+            None => return Err(anyhow!("Difficulty has no stages")),
+            Some(first) if first.from_meters != 0 => {
+                return Err(anyhow!("The first difficulty stage must start at 0 meters"))
+            }
+            Some(_) => {}
+        }
+        if self
+            .stages
+            .windows(2)
+            .any(|pair| pair[0].from_meters >= pair[1].from_meters)
+        {
+            return Err(anyhow!("Difficulty stages must be in increasing order"));
+        }
+        if let Some(stage) = self.stages.iter().find(|stage| stage.segments.is_empty()) {
+            return Err(anyhow!(
+                "Difficulty stage at {} meters has no segments",
+                stage.from_meters
+            ));
+        }
+
+        Ok(self)
+    }
+
+    pub fn stage_at(&self, meters: u32) -> &Stage {
+        self.stages
+            .iter()
+            .rev()
+            .find(|stage| stage.from_meters <= meters)
+            .unwrap_or(&self.stages[0])
+    }
+
+    pub fn lives(&self) -> u8 {
+        self.lives
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn difficulty(json: &str) -> Result<Difficulty> {
+        serde_json::from_str::<Difficulty>(json)?.validate()
+    }
+
+    #[test]
+    fn bundled_difficulty_is_valid() {
+        assert!(difficulty(include_str!("../static/difficulty.json")).is_ok());
+    }
+
+    #[test]
+    fn stage_at_picks_the_last_stage_reached() {
+        let difficulty = difficulty(
+            r#"{"stages": [
+                {"from_meters": 0, "running_speed": 4, "segment_gap": 200, "segments": ["a"]},
+                {"from_meters": 100, "running_speed": 6, "segment_gap": 100, "segments": ["a", "b"]}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(difficulty.stage_at(0).running_speed, 4);
+        assert_eq!(difficulty.stage_at(99).running_speed, 4);
+        assert_eq!(difficulty.stage_at(100).running_speed, 6);
+        assert_eq!(difficulty.stage_at(5000).segment_gap, 100);
+    }
+
+    #[test]
+    fn lives_default_to_one() {
+        let difficulty = difficulty(
+            r#"{"stages": [
+                {"from_meters": 0, "running_speed": 4, "segment_gap": 200, "segments": ["a"]}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(difficulty.lives(), 1);
+    }
+
+    #[test]
+    fn zero_lives_are_rejected() {
+        let result = difficulty(
+            r#"{"lives": 0, "stages": [
+                {"from_meters": 0, "running_speed": 4, "segment_gap": 200, "segments": ["a"]}
+            ]}"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn stages_must_start_at_zero() {
+        let result = difficulty(
+            r#"{"stages": [
+                {"from_meters": 10, "running_speed": 4, "segment_gap": 200, "segments": ["a"]}
+            ]}"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn stages_must_be_in_order() {
+        let result = difficulty(
+            r#"{"stages": [
+                {"from_meters": 0, "running_speed": 4, "segment_gap": 200, "segments": ["a"]},
+                {"from_meters": 200, "running_speed": 6, "segment_gap": 100, "segments": ["a"]},
+                {"from_meters": 100, "running_speed": 5, "segment_gap": 150, "segments": ["a"]}
+            ]}"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn stages_need_segments() {
+        let result = difficulty(
+            r#"{"stages": [
+                {"from_meters": 0, "running_speed": 4, "segment_gap": 200, "segments": []}
+            ]}"#,
+        );
+
+        assert!(result.is_err());
+    }
+}
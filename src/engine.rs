@@ -1,4 +1,6 @@
 use crate::browser::LoopClosure;
+use crate::engine::shapes::{ShapeStyle, Stroke, DEBUG_OUTLINE};
+use crate::engine::text::TextStyle;
 use crate::{browser, sound};
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
@@ -6,13 +8,23 @@ use futures::channel::mpsc::{unbounded, UnboundedReceiver};
 use futures::channel::oneshot::channel;
 use serde::Deserialize;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::sync::Mutex;
 use wasm_bindgen::closure::Closure;
 use wasm_bindgen::{JsCast, JsValue};
 use web_sys::{AudioBuffer, AudioContext, CanvasRenderingContext2d, HtmlElement, HtmlImageElement};
 
+pub mod bitmap_font;
+pub mod camera;
+pub mod effects;
+pub mod parallax;
+pub mod particles;
+pub mod render_queue;
+pub mod shapes;
+pub mod text;
+pub mod world;
+
 #[derive(Deserialize, Clone)]
 pub struct SheetRect {
     pub x: i16,
@@ -50,6 +62,12 @@ pub trait Game {
     fn draw(&self, renderer: &Renderer);
 }
 
+/// Somewhere parts of an image can be copied to.
+pub trait DrawTarget {
+    type Image;
+    fn draw_image(&self, image: &Self::Image, frame: &Rect, destination: &Rect) -> Result<()>;
+}
+
 const FRAME_SIZE: f32 = 1.0 / 60.0 * 1000.0;
 
 pub struct GameLoop {
@@ -59,6 +77,8 @@ pub struct GameLoop {
 
 pub struct Renderer {
     context: CanvasRenderingContext2d,
+    // Subtracted from everything drawn, while drawing through a camera.
+    offset: std::cell::Cell<Point>,
 }
 
 enum KeyPress {
@@ -68,14 +88,141 @@ enum KeyPress {
 
 pub struct KeyState {
     pressed_keys: HashMap<String, web_sys::KeyboardEvent>,
+    just_pressed: HashSet<String>,
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub struct Point {
     pub x: i16,
     pub y: i16,
 }
 
+/// A colour with an alpha from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: f32,
+}
+
+impl Color {
+    pub const fn rgba(red: u8, green: u8, blue: u8, alpha: f32) -> Self {
+        Color {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    pub fn with_alpha(self, alpha: f32) -> Self {
+        Color { alpha, ..self }
+    }
+
+    /// Blends towards `other`, from this colour at 0 to `other` at 1.
+    pub fn mix(self, other: Color, amount: f32) -> Self {
+        let amount = amount.clamp(0.0, 1.0);
+        let channel = |from: u8, to: u8| {
+            (f32::from(from) + (f32::from(to) - f32::from(from)) * amount).round() as u8
+        };
+        Color {
+            red: channel(self.red, other.red),
+            green: channel(self.green, other.green),
+            blue: channel(self.blue, other.blue),
+            alpha: self.alpha + (other.alpha - self.alpha) * amount,
+        }
+    }
+
+    /// The colour as the canvas takes it.
+    pub fn css(&self) -> String {
+        format!(
+            "rgba({}, {}, {}, {:.2})",
+            self.red, self.green, self.blue, self.alpha
+        )
+    }
+}
+
+/// How a drawn image is laid over what is already on the canvas.
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Composite {
+    #[default]
+    SourceOver,
+    /// Brightens where things overlap, for glows and sparks.
+    Lighter,
+    Multiply,
+    Screen,
+}
+
+impl Composite {
+    fn operation(&self) -> &'static str {
+        match self {
+            Composite::SourceOver => "source-over",
+            Composite::Lighter => "lighter",
+            Composite::Multiply => "multiply",
+            Composite::Screen => "screen",
+        }
+    }
+}
+
+/// The default draws an image as it is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawParams {
+    pub flip_horizontally: bool,
+    pub flip_vertically: bool,
+    /// Clockwise, in radians.
+    pub rotation: f32,
+    /// Relative to the destination. Defaults to its middle.
+    pub pivot: Option<Point>,
+    pub scale_x: f32,
+    pub scale_y: f32,
+    pub alpha: f32,
+    pub composite: Composite,
+}
+
+impl Default for DrawParams {
+    fn default() -> Self {
+        DrawParams {
+            flip_horizontally: false,
+            flip_vertically: false,
+            rotation: 0.0,
+            pivot: None,
+            scale_x: 1.0,
+            scale_y: 1.0,
+            alpha: 1.0,
+            composite: Composite::SourceOver,
+        }
+    }
+}
+
+impl DrawParams {
+    fn is_plain(&self) -> bool {
+        *self == DrawParams::default()
+    }
+
+    /// Where the pivot is for an image drawn into `destination`.
+    fn pivot_in(&self, destination: &Rect) -> Point {
+        let pivot = self.pivot.unwrap_or(Point {
+            x: destination.w / 2,
+            y: destination.h / 2,
+        });
+        Point {
+            x: destination.x() + pivot.x,
+            y: destination.y() + pivot.y,
+        }
+    }
+
+    /// The scale with the flips folded in, as the canvas takes it.
+    fn scale(&self) -> (f32, f32) {
+        let flip = |flipped| if flipped { -1.0 } else { 1.0 };
+        (
+            self.scale_x * flip(self.flip_horizontally),
+            self.scale_y * flip(self.flip_vertically),
+        )
+    }
+}
+
 impl SpriteSheet {
     pub fn new(sheet: Sheet, image: HtmlImageElement) -> Self {
         SpriteSheet { sheet, image }
@@ -88,6 +235,16 @@ impl SpriteSheet {
     pub fn draw(&self, renderer: &Renderer, source: &Rect, destination: &Rect) -> Result<()> {
         renderer.draw_image(&self.image, source, destination)
     }
+
+    pub fn draw_with(
+        &self,
+        renderer: &Renderer,
+        source: &Rect,
+        destination: &Rect,
+        params: &DrawParams,
+    ) -> Result<()> {
+        renderer.draw_image_with(&self.image, source, destination, params)
+    }
 }
 
 impl Image {
@@ -108,7 +265,7 @@ impl Image {
     }
 
     pub fn draw_bounding_box(&self, renderer: &Renderer) {
-        renderer.draw_rect(&self.bounding_box)
+        renderer.draw_rect(&self.bounding_box, &DEBUG_OUTLINE)
     }
 
     pub fn right(&self) -> i16 {
@@ -128,6 +285,7 @@ impl KeyState {
     fn new() -> Self {
         KeyState {
             pressed_keys: HashMap::new(),
+            just_pressed: HashSet::new(),
         }
     }
 
@@ -135,12 +293,19 @@ impl KeyState {
         self.pressed_keys.contains_key(code)
     }
 
+    /// Whether the key went down since the last update, ignoring key repeat.
+    pub fn just_pressed(&self, code: &str) -> bool {
+        self.just_pressed.contains(code)
+    }
+
     fn set_pressed(&mut self, code: &str, event: web_sys::KeyboardEvent) {
-        self.pressed_keys.insert(code.into(), event);
+        if self.pressed_keys.insert(code.into(), event).is_none() {
+            self.just_pressed.insert(code.into());
+        }
     }
 
     fn set_released(&mut self, code: &str) {
-        self.pressed_keys.remove(code.into());
+        self.pressed_keys.remove(code);
     }
 }
 
@@ -158,6 +323,7 @@ impl GameLoop {
 
         let renderer = Renderer {
             context: browser::context()?,
+            offset: std::cell::Cell::new(Point::default()),
         };
 
         let f: SharedLoopClosure = Rc::new(RefCell::new(None));
@@ -172,6 +338,7 @@ impl GameLoop {
 
             while game_loop.accumulated_delta > FRAME_SIZE {
                 game.update(&key_state);
+                key_state.just_pressed.clear();
                 game_loop.accumulated_delta -= FRAME_SIZE;
             }
             game_loop.last_frame = perf;
@@ -195,7 +362,7 @@ impl GameLoop {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub struct Rect {
     pub position: Point,
     pub w: i16,
@@ -243,7 +410,34 @@ impl Rect {
     }
 }
 
+impl DrawTarget for Renderer {
+    type Image = HtmlImageElement;
+
+    fn draw_image(&self, image: &HtmlImageElement, frame: &Rect, destination: &Rect) -> Result<()> {
+        Renderer::draw_image(self, image, frame, destination)
+    }
+}
+
 impl Renderer {
+    /// Runs `draw` with everything it draws moved back by `offset`.
+    pub fn with_offset(&self, offset: Point, draw: impl FnOnce(&Renderer)) {
+        let previous = self.offset.get();
+        self.offset.set(Point {
+            x: previous.x + offset.x,
+            y: previous.y + offset.y,
+        });
+        draw(self);
+        self.offset.set(previous);
+    }
+
+    fn on_screen(&self, point: Point) -> Point {
+        let offset = self.offset.get();
+        Point {
+            x: point.x - offset.x,
+            y: point.y - offset.y,
+        }
+    }
+
     pub fn clear(&self, rect: &Rect) {
         self.context.clear_rect(
             rect.x().into(),
@@ -254,6 +448,7 @@ impl Renderer {
     }
 
     pub fn draw_entire_image(&self, image: &HtmlImageElement, position: Point) -> Result<()> {
+        let position = self.on_screen(position);
         self.context
             .draw_image_with_html_image_element(image, position.x.into(), position.y.into())
             .map_err(|e| anyhow!("Failed to draw image: {:#?}", e))
@@ -265,9 +460,14 @@ impl Renderer {
         frame: &Rect,
         destination: &Rect,
     ) -> Result<()> {
+        let destination = Rect::new(
+            self.on_screen(destination.position),
+            destination.w,
+            destination.h,
+        );
         self.context
             .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                &image,
+                image,
                 frame.x().into(),
                 frame.y().into(),
                 frame.w.into(),
@@ -280,26 +480,198 @@ impl Renderer {
             .map_err(|e| anyhow!("Failed to draw image: {:#?}", e))
     }
 
+    pub fn draw_image_with(
+        &self,
+        image: &HtmlImageElement,
+        frame: &Rect,
+        destination: &Rect,
+        params: &DrawParams,
+    ) -> Result<()> {
+        if params.is_plain() {
+            return self.draw_image(image, frame, destination);
+        }
+        let destination = Rect::new(
+            self.on_screen(destination.position),
+            destination.w,
+            destination.h,
+        );
+        let pivot = params.pivot_in(&destination);
+        let (scale_x, scale_y) = params.scale();
+        self.context.save();
+        self.context.set_global_alpha(
+            self.context.global_alpha() * f64::from(params.alpha.clamp(0.0, 1.0)),
+        );
+        let drawn = self
+            .context
+            .set_global_composite_operation(params.composite.operation())
+            .and_then(|_| self.context.translate(pivot.x.into(), pivot.y.into()))
+            .and_then(|_| self.context.rotate(params.rotation.into()))
+            .and_then(|_| self.context.scale(scale_x.into(), scale_y.into()))
+            .and_then(|_| {
+                self.context
+                    .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                        image,
+                        frame.x().into(),
+                        frame.y().into(),
+                        frame.w.into(),
+                        frame.h.into(),
+                        (destination.x() - pivot.x).into(),
+                        (destination.y() - pivot.y).into(),
+                        destination.w.into(),
+                        destination.h.into(),
+                    )
+            })
+            .map_err(|e| anyhow!("Failed to draw image: {:#?}", e));
+        self.context.restore();
+        drawn
+    }
+
+    /// `path` traces in screen coordinates.
     #[allow(deprecated)]
-    pub fn draw_rect(&self, bounding_box: &Rect) {
-        self.context.set_stroke_style(&JsValue::from_str("#FF0000"));
+    fn paint(
+        &self,
+        style: &ShapeStyle,
+        path: impl FnOnce(&CanvasRenderingContext2d) -> std::result::Result<(), JsValue>,
+    ) -> Result<()> {
+        self.context.save();
         self.context.begin_path();
-        self.context.rect(
-            bounding_box.x().into(),
-            bounding_box.y().into(),
-            bounding_box.w.into(),
-            bounding_box.h.into(),
-        );
-        self.context.stroke();
+        let traced = path(&self.context);
+        if traced.is_ok() {
+            if let Some(fill) = style.fill {
+                self.context.set_fill_style(&JsValue::from_str(&fill.css()));
+                self.context.fill();
+            }
+            if let Some(stroke) = style.stroke {
+                self.context
+                    .set_stroke_style(&JsValue::from_str(&stroke.color.css()));
+                self.context.set_line_width(stroke.width.into());
+                self.context.stroke();
+            }
+        }
+        self.context.restore();
+        traced.map_err(|err| anyhow!("Error drawing shape {:#?}", err))
+    }
+
+    pub fn draw_rect(&self, rect: &Rect, style: &ShapeStyle) {
+        let position = self.on_screen(rect.position);
+        // Tracing a plain rectangle can't fail.
+        let _ = self.paint(style, |context| {
+            context.rect(
+                position.x.into(),
+                position.y.into(),
+                rect.w.into(),
+                rect.h.into(),
+            );
+            Ok(())
+        });
+    }
+
+    pub fn draw_rounded_rect(&self, rect: &Rect, radius: i16, style: &ShapeStyle) -> Result<()> {
+        let position = self.on_screen(rect.position);
+        let radius = f64::from(shapes::corner_radius(rect, radius));
+        let (left, top) = (f64::from(position.x), f64::from(position.y));
+        let (right, bottom) = (left + f64::from(rect.w), top + f64::from(rect.h));
+        self.paint(style, |context| {
+            context.move_to(left + radius, top);
+            context.arc_to(right, top, right, bottom, radius)?;
+            context.arc_to(right, bottom, left, bottom, radius)?;
+            context.arc_to(left, bottom, left, top, radius)?;
+            context.arc_to(left, top, right, top, radius)?;
+            context.close_path();
+            Ok(())
+        })
     }
 
     #[allow(dead_code)]
-    pub fn draw_text(&self, text: &str, location: &Point) -> Result<()> {
-        self.context.set_font("16pt serif");
-        self.context
-            .fill_text(text, location.x.into(), location.y.into())
-            .map_err(|err| anyhow!("Error filling text {:#?}", err))?;
-        Ok(())
+    pub fn draw_circle(&self, center: Point, radius: i16, style: &ShapeStyle) -> Result<()> {
+        let center = self.on_screen(center);
+        self.paint(style, |context| {
+            context.arc(
+                center.x.into(),
+                center.y.into(),
+                radius.max(0).into(),
+                0.0,
+                std::f64::consts::TAU,
+            )
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn draw_line(&self, from: Point, to: Point, stroke: &Stroke) {
+        self.draw_polyline(&[from, to], stroke);
+    }
+
+    /// Joins `points` up in order with straight lines.
+    #[allow(dead_code)]
+    pub fn draw_polyline(&self, points: &[Point], stroke: &Stroke) {
+        let style = ShapeStyle {
+            fill: None,
+            stroke: Some(*stroke),
+        };
+        let _ = self.paint(&style, |context| {
+            points.iter().enumerate().for_each(|(index, point)| {
+                let point = self.on_screen(*point);
+                if index == 0 {
+                    context.move_to(point.x.into(), point.y.into());
+                } else {
+                    context.line_to(point.x.into(), point.y.into());
+                }
+            });
+            Ok(())
+        });
+    }
+
+    #[allow(deprecated)]
+    pub fn draw_text(&self, text: &str, location: &Point, style: &TextStyle) -> Result<()> {
+        let location = self.on_screen(*location);
+        self.context.save();
+        self.context.set_font(&style.font());
+        self.context.set_text_align(style.align.css());
+        self.context.set_text_baseline(style.baseline.css());
+        if let Some(shadow) = style.shadow {
+            self.context.set_shadow_color(&shadow.color.css());
+            self.context.set_shadow_offset_x(shadow.offset.x.into());
+            self.context.set_shadow_offset_y(shadow.offset.y.into());
+            self.context.set_shadow_blur(shadow.blur.into());
+        }
+        let drawn = style
+            .outline
+            .map_or(Ok(()), |outline| {
+                self.context
+                    .set_stroke_style(&JsValue::from_str(&outline.color.css()));
+                self.context.set_line_width(outline.width.into());
+                self.context
+                    .stroke_text(text, location.x.into(), location.y.into())
+            })
+            .and_then(|_| {
+                self.context
+                    .set_fill_style(&JsValue::from_str(&style.color.css()));
+                self.context
+                    .fill_text(text, location.x.into(), location.y.into())
+            })
+            .map_err(|err| anyhow!("Error filling text {:#?}", err));
+        self.context.restore();
+        drawn
+    }
+
+    pub fn measure_text(&self, text: &str, style: &TextStyle) -> Result<f64> {
+        self.context.save();
+        self.context.set_font(&style.font());
+        let metrics = self.context.measure_text(text);
+        self.context.restore();
+        metrics
+            .map(|metrics| metrics.width())
+            .map_err(|err| anyhow!("Error measuring text {:#?}", err))
+    }
+
+    pub fn draw_text_box(&self, text: &str, area: &Rect, style: &TextStyle) -> Result<()> {
+        let style = TextStyle {
+            baseline: text::Baseline::Top,
+            ..*style
+        };
+        text::layout(text, area, &style, |line| self.measure_text(line, &style))?
+            .iter()
+            .try_for_each(|(line, location)| self.draw_text(line, location, &style))
     }
 }
 
@@ -321,8 +693,8 @@ pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
         }
     });
 
-    image.set_onload(Some(&success_callback.as_ref().unchecked_ref()));
-    image.set_onerror(Some(&error_callback.as_ref().unchecked_ref()));
+    image.set_onload(Some(success_callback.as_ref().unchecked_ref()));
+    image.set_onerror(Some(error_callback.as_ref().unchecked_ref()));
     image.set_src(source);
 
     complete_rx.await??;
@@ -394,12 +766,17 @@ impl Audio {
         })
     }
 
-    pub fn play_sound(&self, sound: &Sound) -> Result<()> {
-        sound::play_sound(&self.context, &sound.buffer, sound::LOOPING::NO)
+    pub fn play_sound_at(&self, sound: &Sound, x: i16, canvas_width: i16) -> Result<()> {
+        sound::play_panned_sound(
+            &self.context,
+            &sound.buffer,
+            sound::Looping::No,
+            sound::pan_for_position(x, canvas_width),
+        )
     }
 
     pub fn play_looping_sound(&self, sound: &Sound) -> Result<()> {
-        sound::play_sound(&self.context, &sound.buffer, sound::LOOPING::YES)
+        sound::play_sound(&self.context, &sound.buffer, sound::Looping::Yes)
     }
 }
 
@@ -413,9 +790,44 @@ pub fn add_click_handler(elem: HtmlElement) -> UnboundedReceiver<()> {
     click_receiver
 }
 
+unsafe fn draw_frame_rate(renderer: &Renderer, frame_time: f64) {
+    static mut FRAMES_COUNTED: i32 = 0;
+    static mut TOTAL_FRAME_TIME: f64 = 0.0;
+    static mut FRAME_RATE: i32 = 0;
+
+    FRAMES_COUNTED += 1;
+    TOTAL_FRAME_TIME += frame_time;
+
+    if TOTAL_FRAME_TIME > 1000.0 {
+        FRAME_RATE = FRAMES_COUNTED;
+        TOTAL_FRAME_TIME = 0.0;
+        FRAMES_COUNTED = 0;
+    }
+
+    let frame_rate = FRAME_RATE;
+    if let Err(err) = renderer.draw_text(
+        &format!("Frame Rate {}", frame_rate),
+        &Point { x: 590, y: 100 },
+        &TextStyle {
+            align: text::Align::Right,
+            ..TextStyle::DEFAULT
+        },
+    ) {
+        error!("Could not draw text {:#?}", err);
+    }
+}
+
 #[cfg(test)]
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
+    use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+    use web_sys::HtmlCanvasElement;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    const RED: Color = Color::rgba(255, 0, 0, 1.0);
 
     #[test]
     fn two_rects_that_intersect_on_the_left() {
@@ -464,26 +876,150 @@ mod tests {
 
         assert_eq!(rect2.interests(&rect1), false)
     }
-}
 
-unsafe fn draw_frame_rate(renderer: &Renderer, frame_time: f64) {
-    static mut FRAMES_COUNTED: i32 = 0;
-    static mut TOTAL_FRAME_TIME: f64 = 0.0;
-    static mut FRAME_RATE: i32 = 0;
+    #[test]
+    fn colors_mix_every_channel_and_the_alpha() {
+        let red = Color::rgba(255, 0, 0, 1.0);
+        let blue = Color::rgba(0, 0, 255, 0.0);
 
-    FRAMES_COUNTED += 1;
-    TOTAL_FRAME_TIME += frame_time;
+        assert_eq!(red.mix(blue, 0.5), Color::rgba(128, 0, 128, 0.5));
+        assert_eq!(red.mix(blue, 2.0), blue);
+    }
 
-    if TOTAL_FRAME_TIME > 1000.0 {
-        FRAME_RATE = FRAMES_COUNTED;
-        TOTAL_FRAME_TIME = 0.0;
-        FRAMES_COUNTED = 0;
+    #[test]
+    fn colors_are_written_for_the_canvas() {
+        assert_eq!(Color::rgba(255, 80, 0, 0.5).css(), "rgba(255, 80, 0, 0.50)");
     }
 
-    if let Err(err) = renderer.draw_text(
-        &format!("Frame Rate {}", FRAME_RATE),
-        &Point { x: 400, y: 100},
-    ) {
-        error!("Could not draw text {:#?}", err);
+    #[test]
+    fn default_draw_params_draw_the_image_as_it_is() {
+        assert!(DrawParams::default().is_plain());
+        assert!(!DrawParams {
+            alpha: 0.5,
+            ..DrawParams::default()
+        }
+        .is_plain());
+    }
+
+    #[test]
+    fn default_draw_params_draw_over_what_is_there() {
+        let params = DrawParams::default();
+
+        assert_eq!(params.composite, Composite::SourceOver);
+        assert_eq!(params.composite.operation(), "source-over");
+        assert!(!DrawParams {
+            composite: Composite::Lighter,
+            ..params
+        }
+        .is_plain());
+    }
+
+    #[test]
+    fn images_turn_about_their_middle_unless_given_a_pivot() {
+        let destination = Rect::new_from_x_y(100, 50, 40, 20);
+        let pinned = DrawParams {
+            pivot: Some(Point { x: 0, y: 20 }),
+            ..DrawParams::default()
+        };
+
+        assert_eq!(
+            DrawParams::default().pivot_in(&destination),
+            Point { x: 120, y: 60 }
+        );
+        assert_eq!(pinned.pivot_in(&destination), Point { x: 100, y: 70 });
+    }
+
+    #[test]
+    fn flips_turn_the_scale_around() {
+        let params = DrawParams {
+            flip_horizontally: true,
+            scale_x: 2.0,
+            scale_y: 0.5,
+            ..DrawParams::default()
+        };
+
+        assert_eq!(params.scale(), (-2.0, 0.5));
+    }
+
+    fn blank_renderer() -> Renderer {
+        let canvas = browser::document()
+            .unwrap()
+            .create_element("canvas")
+            .unwrap()
+            .dyn_into::<HtmlCanvasElement>()
+            .unwrap();
+        canvas.set_width(40);
+        canvas.set_height(40);
+        let context = canvas
+            .get_context("2d")
+            .unwrap()
+            .unwrap()
+            .dyn_into::<CanvasRenderingContext2d>()
+            .unwrap();
+        Renderer {
+            context,
+            offset: std::cell::Cell::new(Point::default()),
+        }
+    }
+
+    fn is_red(renderer: &Renderer, x: i16, y: i16) -> bool {
+        let pixel = renderer
+            .context
+            .get_image_data(x.into(), y.into(), 1.0, 1.0)
+            .unwrap()
+            .data();
+        pixel[0] == 255 && pixel[3] == 255
+    }
+
+    #[wasm_bindgen_test]
+    fn circles_fill_around_their_center() {
+        let renderer = blank_renderer();
+
+        renderer
+            .draw_circle(Point { x: 20, y: 20 }, 10, &ShapeStyle::filled(RED))
+            .unwrap();
+
+        assert!(is_red(&renderer, 20, 20));
+        assert!(is_red(&renderer, 27, 20));
+        assert!(!is_red(&renderer, 28, 28));
+    }
+
+    #[wasm_bindgen_test]
+    fn lines_are_stroked_from_end_to_end() {
+        let renderer = blank_renderer();
+
+        renderer.draw_line(
+            Point { x: 0, y: 20 },
+            Point { x: 40, y: 20 },
+            &Stroke {
+                color: RED,
+                width: 4.0,
+            },
+        );
+
+        assert!(is_red(&renderer, 5, 19));
+        assert!(is_red(&renderer, 35, 19));
+        assert!(!is_red(&renderer, 20, 30));
+    }
+
+    #[wasm_bindgen_test]
+    fn polylines_join_their_points_in_order_without_closing() {
+        let renderer = blank_renderer();
+
+        renderer.draw_polyline(
+            &[
+                Point { x: 5, y: 5 },
+                Point { x: 5, y: 35 },
+                Point { x: 35, y: 35 },
+            ],
+            &Stroke {
+                color: RED,
+                width: 4.0,
+            },
+        );
+
+        assert!(is_red(&renderer, 5, 20));
+        assert!(is_red(&renderer, 20, 35));
+        assert!(!is_red(&renderer, 20, 20));
     }
 }
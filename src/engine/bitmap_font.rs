@@ -0,0 +1,292 @@
+use crate::engine::{DrawTarget, Point, Rect};
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::HashMap;
+use web_sys::HtmlImageElement;
+
+/// Glyph metrics in the JSON form of the BMFont format.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FontDefinition {
+    pub common: Common,
+    pub chars: Vec<GlyphDefinition>,
+    #[serde(default)]
+    pub kernings: Vec<Kerning>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Common {
+    pub line_height: i16,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct GlyphDefinition {
+    /// A unicode code point.
+    pub id: u32,
+    pub x: i16,
+    pub y: i16,
+    pub width: i16,
+    pub height: i16,
+    #[serde(default)]
+    pub xoffset: i16,
+    #[serde(default)]
+    pub yoffset: i16,
+    pub xadvance: i16,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Kerning {
+    pub first: u32,
+    pub second: u32,
+    pub amount: i16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GlyphDraw {
+    pub source: Rect,
+    pub destination: Rect,
+}
+
+/// Lays out text without the image, so it can be tested natively.
+pub struct FontMetrics {
+    glyphs: HashMap<char, GlyphDefinition>,
+    kernings: HashMap<(char, char), i16>,
+    line_height: i16,
+}
+
+impl FontMetrics {
+    pub fn new(definition: &FontDefinition) -> Self {
+        let glyphs = definition
+            .chars
+            .iter()
+            .filter_map(|glyph| Some((char::from_u32(glyph.id)?, *glyph)))
+            .collect();
+        let kernings = definition
+            .kernings
+            .iter()
+            .filter_map(|kerning| {
+                let first = char::from_u32(kerning.first)?;
+                let second = char::from_u32(kerning.second)?;
+                Some(((first, second), kerning.amount))
+            })
+            .collect();
+        FontMetrics {
+            glyphs,
+            kernings,
+            line_height: definition.common.line_height,
+        }
+    }
+
+    /// Characters the font doesn't have are left out.
+    pub fn layout(&self, text: &str, position: Point) -> Vec<GlyphDraw> {
+        let mut draws = vec![];
+        for (row, line) in text.split('\n').enumerate() {
+            let y = position.y + row as i16 * self.line_height;
+            let mut x = position.x;
+            let mut previous = None;
+            for character in line.chars() {
+                let Some(glyph) = self.glyphs.get(&character) else {
+                    continue;
+                };
+                if let Some(previous) = previous {
+                    x += self.kerning(previous, character);
+                }
+                draws.push(GlyphDraw {
+                    source: Rect::new_from_x_y(glyph.x, glyph.y, glyph.width, glyph.height),
+                    destination: Rect::new_from_x_y(
+                        x + glyph.xoffset,
+                        y + glyph.yoffset,
+                        glyph.width,
+                        glyph.height,
+                    ),
+                });
+                x += glyph.xadvance;
+                previous = Some(character);
+            }
+        }
+        draws
+    }
+
+    pub fn width(&self, text: &str) -> i16 {
+        text.split('\n')
+            .map(|line| {
+                let mut previous = None;
+                line.chars()
+                    .filter_map(|character| {
+                        let glyph = self.glyphs.get(&character)?;
+                        let kerning =
+                            previous.map_or(0, |previous| self.kerning(previous, character));
+                        previous = Some(character);
+                        Some(kerning + glyph.xadvance)
+                    })
+                    .sum()
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn kerning(&self, first: char, second: char) -> i16 {
+        self.kernings.get(&(first, second)).copied().unwrap_or(0)
+    }
+}
+
+pub struct BitmapFont<I = HtmlImageElement> {
+    image: I,
+    metrics: FontMetrics,
+}
+
+impl<I> BitmapFont<I> {
+    pub fn new(definition: &FontDefinition, image: I) -> Self {
+        BitmapFont {
+            image,
+            metrics: FontMetrics::new(definition),
+        }
+    }
+
+    pub fn width(&self, text: &str) -> i16 {
+        self.metrics.width(text)
+    }
+
+    pub fn line_height(&self) -> i16 {
+        self.metrics.line_height
+    }
+
+    /// `position` is the top left of the first line.
+    pub fn draw(
+        &self,
+        target: &impl DrawTarget<Image = I>,
+        text: &str,
+        position: Point,
+    ) -> Result<()> {
+        self.metrics
+            .layout(text, position)
+            .iter()
+            .try_for_each(|glyph| target.draw_image(&self.image, &glyph.source, &glyph.destination))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct Recorder {
+        draws: RefCell<Vec<(&'static str, Rect, Rect)>>,
+    }
+
+    impl DrawTarget for Recorder {
+        type Image = &'static str;
+
+        fn draw_image(&self, image: &&'static str, frame: &Rect, destination: &Rect) -> Result<()> {
+            self.draws.borrow_mut().push((image, *frame, *destination));
+            Ok(())
+        }
+    }
+
+    fn definition() -> FontDefinition {
+        serde_json::from_str(
+            r#"{
+                "common": {"lineHeight": 30, "base": 21},
+                "chars": [
+                    {"id": 65, "x": 0, "y": 0, "width": 20, "height": 27, "xoffset": -3, "yoffset": -3, "xadvance": 18},
+                    {"id": 86, "x": 20, "y": 0, "width": 20, "height": 27, "xoffset": -3, "yoffset": -3, "xadvance": 18},
+                    {"id": 49, "x": 40, "y": 0, "width": 14, "height": 27, "xadvance": 12}
+                ],
+                "kernings": [{"first": 65, "second": 86, "amount": -3}]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn metrics() -> FontMetrics {
+        FontMetrics::new(&definition())
+    }
+
+    fn positions(draws: &[GlyphDraw]) -> Vec<(i16, i16)> {
+        draws
+            .iter()
+            .map(|draw| (draw.destination.x(), draw.destination.y()))
+            .collect()
+    }
+
+    #[test]
+    fn glyphs_are_copied_from_their_place_on_the_image() {
+        let draws = metrics().layout("V1", Point { x: 10, y: 10 });
+
+        assert_eq!(draws[0].source, Rect::new_from_x_y(20, 0, 20, 27));
+        assert_eq!(draws[0].destination, Rect::new_from_x_y(7, 7, 20, 27));
+        assert_eq!(draws[1].source, Rect::new_from_x_y(40, 0, 14, 27));
+        assert_eq!(draws[1].destination, Rect::new_from_x_y(28, 10, 14, 27));
+    }
+
+    #[test]
+    fn drawing_copies_each_glyph_from_the_font_image() {
+        let font = BitmapFont::new(&definition(), "font.png");
+        let recorder = Recorder::default();
+
+        font.draw(&recorder, "V1", Point { x: 10, y: 10 }).unwrap();
+
+        assert_eq!(
+            recorder.draws.into_inner(),
+            vec![
+                (
+                    "font.png",
+                    Rect::new_from_x_y(20, 0, 20, 27),
+                    Rect::new_from_x_y(7, 7, 20, 27)
+                ),
+                (
+                    "font.png",
+                    Rect::new_from_x_y(40, 0, 14, 27),
+                    Rect::new_from_x_y(28, 10, 14, 27)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn kerned_pairs_are_pulled_together() {
+        let metrics = metrics();
+
+        assert_eq!(
+            positions(&metrics.layout("AV", Point { x: 0, y: 0 })),
+            vec![(-3, -3), (12, -3)]
+        );
+        assert_eq!(
+            positions(&metrics.layout("VA", Point { x: 0, y: 0 })),
+            vec![(-3, -3), (15, -3)]
+        );
+        assert_eq!(metrics.width("AV"), 33);
+    }
+
+    #[test]
+    fn line_breaks_start_a_new_line_below() {
+        let metrics = metrics();
+
+        assert_eq!(
+            positions(&metrics.layout("1\n11", Point { x: 0, y: 0 })),
+            vec![(0, 0), (0, 30), (12, 30)]
+        );
+        assert_eq!(metrics.width("1\n11"), 24);
+    }
+
+    #[test]
+    fn characters_the_font_lacks_are_left_out() {
+        let metrics = metrics();
+
+        assert_eq!(metrics.layout("A?1", Point { x: 0, y: 0 }).len(), 2);
+        assert_eq!(metrics.width("A?1"), 30);
+    }
+
+    #[test]
+    fn bundled_font_has_every_character_the_hud_uses() {
+        let definition: FontDefinition =
+            serde_json::from_str(include_str!("../../static/font.json")).unwrap();
+        let metrics = FontMetrics::new(&definition);
+        let text = "DISTANCE 0123456789M\nOBSTACLES SCORE LIVES";
+
+        let glyphs = text.chars().filter(|character| *character != '\n').count();
+
+        assert_eq!(metrics.layout(text, Point { x: 0, y: 0 }).len(), glyphs);
+    }
+}
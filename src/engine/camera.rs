@@ -0,0 +1,136 @@
+use crate::engine::{Point, Rect};
+
+/// Which part of the world is on screen.
+#[derive(Clone, Copy)]
+pub struct Camera {
+    position: Point,
+    width: i16,
+    height: i16,
+    look_ahead: i16,
+    dead_zone_top: i16,
+    dead_zone_bottom: i16,
+}
+
+impl Camera {
+    pub fn new(width: i16, height: i16) -> Self {
+        Camera {
+            position: Point { x: 0, y: 0 },
+            width,
+            height,
+            look_ahead: 0,
+            dead_zone_top: height / 2,
+            dead_zone_bottom: height / 2,
+        }
+    }
+
+    /// How far left of the middle of the screen the target is kept.
+    pub fn with_look_ahead(mut self, look_ahead: i16) -> Self {
+        self.look_ahead = look_ahead;
+        self
+    }
+
+    /// The target can move between `top` and `bottom` before the camera follows.
+    pub fn with_dead_zone(mut self, top: i16, bottom: i16) -> Self {
+        self.dead_zone_top = top;
+        self.dead_zone_bottom = bottom;
+        self
+    }
+
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    pub fn view(&self) -> Rect {
+        Rect::new(self.position, self.width, self.height)
+    }
+
+    /// Never goes below y 0, where the bottom of the world is in view.
+    pub fn follow(&mut self, target: Point) {
+        self.position.x = target.x + self.look_ahead - self.width / 2;
+        let screen_y = target.y - self.position.y;
+        if screen_y < self.dead_zone_top {
+            self.position.y = target.y - self.dead_zone_top;
+        } else if screen_y > self.dead_zone_bottom {
+            self.position.y = target.y - self.dead_zone_bottom;
+        }
+        self.position.y = self.position.y.min(0);
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.position.x += x;
+    }
+
+    pub fn on_screen(&self, point: Point) -> Point {
+        Point {
+            x: point.x - self.position.x,
+            y: point.y - self.position.y,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> Camera {
+        Camera::new(600, 680)
+            .with_look_ahead(230)
+            .with_dead_zone(100, 400)
+    }
+
+    #[test]
+    fn the_target_is_kept_behind_the_middle_of_the_screen() {
+        let mut camera = camera();
+
+        camera.follow(Point { x: 1070, y: 479 });
+
+        assert_eq!(camera.position().x, 1000);
+        assert_eq!(camera.on_screen(Point { x: 1070, y: 479 }).x, 70);
+    }
+
+    #[test]
+    fn the_camera_stays_put_while_the_target_is_in_the_dead_zone() {
+        let mut camera = camera();
+
+        camera.follow(Point { x: 70, y: 479 });
+        camera.follow(Point { x: 70, y: 167 });
+
+        assert_eq!(camera.position().y, 0);
+    }
+
+    #[test]
+    fn the_camera_rises_with_a_target_above_the_dead_zone() {
+        let mut camera = camera();
+
+        camera.follow(Point { x: 70, y: 20 });
+        assert_eq!(camera.position().y, -80);
+
+        camera.follow(Point { x: 70, y: 60 });
+        assert_eq!(camera.position().y, -80);
+
+        camera.follow(Point { x: 70, y: 479 });
+        assert_eq!(camera.position().y, 0);
+    }
+
+    #[test]
+    fn the_camera_never_drops_below_the_bottom_of_the_world() {
+        let mut camera = camera();
+
+        camera.follow(Point { x: 70, y: 900 });
+
+        assert_eq!(camera.position().y, 0);
+    }
+
+    #[test]
+    fn the_view_is_the_screen_at_the_camera_position() {
+        let mut camera = camera();
+        camera.move_horizontally(250);
+
+        let view = camera.view();
+
+        assert_eq!(
+            (view.x(), view.y(), view.right(), view.bottom()),
+            (250, 0, 850, 680)
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::engine::shapes::ShapeStyle;
+use crate::engine::{Color, Point, Rect, Renderer};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+const MAX_SHAKE: f32 = 12.0;
+const TRAUMA_DECAY: f32 = 0.02;
+
+/// A colour laid over the whole screen that fades out over `frames`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Flash {
+    pub color: Color,
+    pub frames: u8,
+}
+
+/// Screen shake, hit-stop and flashes. Shake comes from trauma, which wears
+/// off by itself.
+pub struct Effects {
+    rng: StdRng,
+    trauma: f32,
+    shake: Point,
+    frozen_frames: u8,
+    flash: Option<Flash>,
+    flash_frames: u8,
+}
+
+impl Effects {
+    pub fn new(seed: u64) -> Self {
+        Effects {
+            rng: StdRng::seed_from_u64(seed),
+            trauma: 0.0,
+            shake: Point::default(),
+            frozen_frames: 0,
+            flash: None,
+            flash_frames: 0,
+        }
+    }
+
+    /// Adds to how hard the screen shakes, up to a maximum of 1.
+    pub fn add_trauma(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0.0, 1.0);
+    }
+
+    /// Freezes the game for `frames` updates.
+    pub fn hit_stop(&mut self, frames: u8) {
+        self.frozen_frames = self.frozen_frames.max(frames);
+    }
+
+    pub fn flash(&mut self, flash: Flash) {
+        self.flash_frames = flash.frames;
+        self.flash = Some(flash);
+    }
+
+    /// Called once per update, frozen or not.
+    pub fn update(&mut self) {
+        self.trauma = (self.trauma - TRAUMA_DECAY).max(0.0);
+        let strength = MAX_SHAKE * self.trauma * self.trauma;
+        self.shake = if strength > 0.0 {
+            Point {
+                x: (strength * self.rng.gen_range(-1.0..=1.0)).round() as i16,
+                y: (strength * self.rng.gen_range(-1.0..=1.0)).round() as i16,
+            }
+        } else {
+            Point::default()
+        };
+        self.flash_frames = self.flash_frames.saturating_sub(1);
+        if self.flash_frames == 0 {
+            self.flash = None;
+        }
+    }
+
+    /// Uses up one frame of hit-stop, if there is one.
+    pub fn take_frozen_frame(&mut self) -> bool {
+        if self.frozen_frames > 0 {
+            self.frozen_frames -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// How far to move everything drawn this frame.
+    pub fn shake(&self) -> Point {
+        self.shake
+    }
+
+    fn flash_color(&self) -> Option<Color> {
+        self.flash.map(|flash| {
+            let remaining = f32::from(self.flash_frames) / f32::from(flash.frames.max(1));
+            flash.color.with_alpha(flash.color.alpha * remaining)
+        })
+    }
+
+    pub fn draw(&self, renderer: &Renderer, screen: &Rect) {
+        if let Some(color) = self.flash_color() {
+            renderer.draw_rect(screen, &ShapeStyle::filled(color));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shakes(effects: &mut Effects, frames: usize) -> Vec<(i16, i16)> {
+        (0..frames)
+            .map(|_| {
+                effects.update();
+                (effects.shake().x, effects.shake().y)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_same_seed_shakes_the_same_way() {
+        let mut first = Effects::new(7);
+        let mut second = Effects::new(7);
+        first.add_trauma(1.0);
+        second.add_trauma(1.0);
+
+        assert_eq!(shakes(&mut first, 30), shakes(&mut second, 30));
+    }
+
+    #[test]
+    fn shake_dies_away_as_trauma_wears_off() {
+        let mut effects = Effects::new(7);
+        effects.add_trauma(0.5);
+
+        let shakes = shakes(&mut effects, 40);
+
+        assert!(shakes.iter().all(|(x, y)| x.abs() <= 3 && y.abs() <= 3));
+        assert_eq!(shakes.last(), Some(&(0, 0)));
+    }
+
+    #[test]
+    fn trauma_is_capped() {
+        let mut doubled = Effects::new(7);
+        doubled.add_trauma(0.8);
+        doubled.add_trauma(0.8);
+        let mut full = Effects::new(7);
+        full.add_trauma(1.0);
+
+        assert_eq!(shakes(&mut doubled, 30), shakes(&mut full, 30));
+    }
+
+    #[test]
+    fn hit_stop_freezes_for_its_frames() {
+        let mut effects = Effects::new(7);
+        effects.hit_stop(3);
+        effects.hit_stop(2);
+
+        let frozen: Vec<bool> = (0..4).map(|_| effects.take_frozen_frame()).collect();
+
+        assert_eq!(frozen, vec![true, true, true, false]);
+    }
+
+    #[test]
+    fn flashes_fade_out() {
+        let mut effects = Effects::new(7);
+        effects.flash(Flash {
+            color: Color::rgba(255, 255, 255, 0.8),
+            frames: 4,
+        });
+
+        effects.update();
+        let early = effects.flash_color().unwrap().alpha;
+        effects.update();
+        effects.update();
+        let late = effects.flash_color().unwrap().alpha;
+        effects.update();
+
+        assert!(late < early);
+        assert!(effects.flash_color().is_none());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::engine::{self, Point, Renderer};
+use anyhow::Result;
+use serde::Deserialize;
+use web_sys::HtmlImageElement;
+
+/// Background layers, back to front, each scrolling at its own speed.
+#[derive(Deserialize)]
+pub struct ParallaxDefinition {
+    pub layers: Vec<LayerDefinition>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct LayerDefinition {
+    pub image: String,
+    /// 0 stays put and 1 keeps up with the ground.
+    #[serde(default = "full_speed")]
+    pub scroll_factor: f32,
+    #[serde(default)]
+    pub y: i16,
+    #[serde(default = "tiled")]
+    pub tiled: bool,
+}
+
+fn full_speed() -> f32 {
+    1.0
+}
+
+fn tiled() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct LayerScroll {
+    scroll_factor: f32,
+    tiled: bool,
+    width: i16,
+    offset: f32,
+}
+
+impl LayerScroll {
+    fn new(definition: &LayerDefinition, width: i16) -> Self {
+        LayerScroll {
+            scroll_factor: definition.scroll_factor,
+            tiled: definition.tiled,
+            width,
+            offset: 0.0,
+        }
+    }
+
+    fn scroll(&mut self, distance: i16) {
+        self.offset += f32::from(distance) * self.scroll_factor;
+        if self.tiled && self.width > 0 {
+            self.offset %= f32::from(self.width);
+        }
+    }
+
+    /// The x of every copy of the layer that is at least partly on screen.
+    fn positions(&self, view_width: i16) -> Vec<i16> {
+        let offset = self.offset.round() as i16;
+        if !self.tiled || self.width <= 0 {
+            return vec![offset];
+        }
+        let first = match offset.rem_euclid(self.width) {
+            0 => 0,
+            start => start - self.width,
+        };
+        (0..)
+            .map(|copy| first + copy * self.width)
+            .take_while(|x| *x < view_width)
+            .collect()
+    }
+}
+
+struct Layer {
+    image: HtmlImageElement,
+    y: i16,
+    scroll: LayerScroll,
+}
+
+pub struct ParallaxBackground {
+    layers: Vec<Layer>,
+    view_width: i16,
+}
+
+impl ParallaxBackground {
+    pub async fn load(definition: &ParallaxDefinition, view_width: i16) -> Result<Self> {
+        let mut layers = vec![];
+        for layer in &definition.layers {
+            let image = engine::load_image(&layer.image).await?;
+            layers.push(Layer {
+                scroll: LayerScroll::new(layer, image.width() as i16),
+                y: layer.y,
+                image,
+            });
+        }
+        Ok(ParallaxBackground { layers, view_width })
+    }
+
+    #[cfg(test)]
+    pub fn empty(view_width: i16) -> Self {
+        ParallaxBackground {
+            layers: vec![],
+            view_width,
+        }
+    }
+
+    /// `distance` is how far the ground moved this frame.
+    pub fn scroll(&mut self, distance: i16) {
+        self.layers
+            .iter_mut()
+            .for_each(|layer| layer.scroll.scroll(distance));
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        self.layers.iter().for_each(|layer| {
+            layer
+                .scroll
+                .positions(self.view_width)
+                .into_iter()
+                .for_each(|x| {
+                    if let Err(err) =
+                        renderer.draw_entire_image(&layer.image, Point { x, y: layer.y })
+                    {
+                        error!("Could not draw background layer {:#?}", err);
+                    }
+                })
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(scroll_factor: f32, tiled: bool) -> LayerScroll {
+        LayerScroll::new(
+            &LayerDefinition {
+                image: "BG.png".into(),
+                scroll_factor,
+                y: 0,
+                tiled,
+            },
+            400,
+        )
+    }
+
+    #[test]
+    fn layers_default_to_tiling_at_full_speed() {
+        let definition: LayerDefinition = serde_json::from_str(r#"{"image": "BG.png"}"#).unwrap();
+
+        assert_eq!(definition.scroll_factor, 1.0);
+        assert_eq!(definition.y, 0);
+        assert!(definition.tiled);
+    }
+
+    #[test]
+    fn layers_scroll_by_their_share_of_the_distance() {
+        let mut far = layer(0.25, true);
+        let mut near = layer(1.0, true);
+
+        (0..8).for_each(|_| {
+            far.scroll(-4);
+            near.scroll(-4);
+        });
+
+        assert_eq!(far.positions(600)[0], -8);
+        assert_eq!(near.positions(600)[0], -32);
+    }
+
+    #[test]
+    fn tiled_layers_cover_the_whole_view() {
+        let mut scroll = layer(1.0, true);
+        scroll.scroll(-150);
+
+        assert_eq!(scroll.positions(600), vec![-150, 250]);
+
+        scroll.scroll(-300);
+
+        assert_eq!(scroll.positions(600), vec![-50, 350]);
+    }
+
+    #[test]
+    fn untiled_layers_scroll_off_the_screen() {
+        let mut scroll = layer(1.0, false);
+        scroll.scroll(-450);
+
+        assert_eq!(scroll.positions(600), vec![-450]);
+    }
+
+    #[test]
+    fn bundled_background_is_valid() {
+        let definition: ParallaxDefinition =
+            serde_json::from_str(include_str!("../../static/background.json")).unwrap();
+
+        assert_eq!(definition.layers.len(), 2);
+        assert!(definition.layers[0].scroll_factor < definition.layers[1].scroll_factor);
+        assert!(definition.layers[0].y < definition.layers[1].y);
+    }
+}
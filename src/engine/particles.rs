@@ -0,0 +1,385 @@
+use crate::engine::shapes::ShapeStyle;
+use crate::engine::{Color, DrawParams, Rect, Renderer, SpriteSheet};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+const SHADES: usize = 16;
+
+#[derive(Clone, Debug)]
+pub struct ParticleStyle {
+    pub lifetime: u16,
+    /// In pixels per frame.
+    pub velocity_x: RangeInclusive<f32>,
+    pub velocity_y: RangeInclusive<f32>,
+    pub gravity: f32,
+    pub size: i16,
+    pub start_color: Color,
+    pub end_color: Color,
+    /// Cells played over a particle's life. Without any, particles are squares.
+    pub frames: Vec<String>,
+}
+
+struct CompiledStyle {
+    style: ParticleStyle,
+    shades: Vec<Color>,
+}
+
+impl CompiledStyle {
+    fn new(style: ParticleStyle) -> Self {
+        let shades: Vec<Color> = (0..SHADES)
+            .map(|shade| {
+                style
+                    .start_color
+                    .mix(style.end_color, shade as f32 / (SHADES - 1) as f32)
+            })
+            .collect();
+        CompiledStyle { style, shades }
+    }
+
+    fn shade(&self, particle: &Particle) -> usize {
+        usize::from(particle.age) * (SHADES - 1) / usize::from(self.style.lifetime.max(1))
+    }
+
+    fn frame(&self, particle: &Particle) -> Option<&str> {
+        let frames = &self.style.frames;
+        let index =
+            usize::from(particle.age) * frames.len() / usize::from(self.style.lifetime.max(1));
+        frames
+            .get(index.min(frames.len().saturating_sub(1)))
+            .map(String::as_str)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StyleId(usize);
+
+#[derive(Clone, Copy, Default)]
+struct Particle {
+    style: usize,
+    x: f32,
+    y: f32,
+    velocity_x: f32,
+    velocity_y: f32,
+    age: u16,
+    alive: bool,
+}
+
+/// A fixed pool of particles. Nothing is made when it is full.
+pub struct ParticleSystem {
+    styles: Vec<CompiledStyle>,
+    particles: Vec<Particle>,
+    sheet: Option<Rc<SpriteSheet>>,
+    rng: StdRng,
+}
+
+impl ParticleSystem {
+    pub fn new(budget: usize, seed: u64) -> Self {
+        ParticleSystem {
+            styles: vec![],
+            particles: vec![Particle::default(); budget],
+            sheet: None,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Where the cells in each style's `frames` come from.
+    pub fn with_sheet(mut self, sheet: Rc<SpriteSheet>) -> Self {
+        self.sheet = Some(sheet);
+        self
+    }
+
+    pub fn add_style(&mut self, style: ParticleStyle) -> StyleId {
+        self.styles.push(CompiledStyle::new(style));
+        StyleId(self.styles.len() - 1)
+    }
+
+    pub fn emit(&mut self, style: StyleId, x: i16, y: i16, count: u16) {
+        let Some(compiled) = self.styles.get(style.0) else {
+            error!("Particle style {:?} not found", style);
+            return;
+        };
+        let free = self.particles.iter_mut().filter(|particle| !particle.alive);
+        for particle in free.take(usize::from(count)) {
+            *particle = Particle {
+                style: style.0,
+                x: f32::from(x),
+                y: f32::from(y),
+                velocity_x: self.rng.gen_range(compiled.style.velocity_x.clone()),
+                velocity_y: self.rng.gen_range(compiled.style.velocity_y.clone()),
+                age: 0,
+                alive: true,
+            };
+        }
+    }
+
+    pub fn update(&mut self) {
+        let styles = &self.styles;
+        self.particles
+            .iter_mut()
+            .filter(|particle| particle.alive)
+            .for_each(|particle| {
+                let style = &styles[particle.style].style;
+                particle.velocity_y += style.gravity;
+                particle.x += particle.velocity_x;
+                particle.y += particle.velocity_y;
+                particle.age += 1;
+                particle.alive = particle.age < style.lifetime;
+            });
+    }
+
+    #[cfg(test)]
+    pub fn live_count(&self) -> usize {
+        self.particles
+            .iter()
+            .filter(|particle| particle.alive)
+            .count()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles
+            .iter_mut()
+            .for_each(|particle| particle.alive = false);
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        self.particles
+            .iter()
+            .filter(|particle| particle.alive)
+            .for_each(|particle| self.draw_particle(renderer, particle));
+    }
+
+    fn draw_particle(&self, renderer: &Renderer, particle: &Particle) {
+        let compiled = &self.styles[particle.style];
+        let shade = compiled.shade(particle);
+        let x = particle.x.round() as i16;
+        let y = particle.y.round() as i16;
+        let cell = compiled
+            .frame(particle)
+            .and_then(|name| Some((self.sheet.as_ref()?, name)));
+        match cell {
+            Some((sheet, name)) => {
+                let Some(cell) = sheet.cell(name) else {
+                    error!("Particle frame {} not found", name);
+                    return;
+                };
+                let frame = &cell.frame;
+                if let Err(err) = sheet.draw_with(
+                    renderer,
+                    &Rect::new_from_x_y(frame.x, frame.y, frame.w, frame.h),
+                    &Rect::new_from_x_y(x - frame.w / 2, y - frame.h / 2, frame.w, frame.h),
+                    &DrawParams {
+                        alpha: compiled.shades[shade].alpha,
+                        ..DrawParams::default()
+                    },
+                ) {
+                    error!("Could not draw particle {:#?}", err);
+                }
+            }
+            None => {
+                let size = compiled.style.size;
+                renderer.draw_rect(
+                    &Rect::new_from_x_y(x - size / 2, y - size / 2, size, size),
+                    &ShapeStyle::filled(compiled.shades[shade]),
+                );
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Emission {
+    Burst(u16),
+    /// Particles a frame, which can be a fraction.
+    Continuous(f32),
+}
+
+pub struct Emitter {
+    style: StyleId,
+    emission: Emission,
+    owed: f32,
+}
+
+impl Emitter {
+    pub fn new(style: StyleId, emission: Emission) -> Self {
+        Emitter {
+            style,
+            emission,
+            owed: 0.0,
+        }
+    }
+
+    /// Continuous emitters make one frame's share per call.
+    pub fn emit(&mut self, particles: &mut ParticleSystem, x: i16, y: i16) {
+        let count = match self.emission {
+            Emission::Burst(count) => count,
+            Emission::Continuous(per_frame) => {
+                self.owed += per_frame;
+                let count = self.owed.floor();
+                self.owed -= count;
+                count as u16
+            }
+        };
+        particles.emit(self.style, x, y, count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spark() -> ParticleStyle {
+        ParticleStyle {
+            lifetime: 10,
+            velocity_x: -2.0..=2.0,
+            velocity_y: -4.0..=-1.0,
+            gravity: 0.5,
+            size: 4,
+            start_color: Color::rgba(255, 255, 0, 1.0),
+            end_color: Color::rgba(255, 0, 0, 0.0),
+            frames: vec![],
+        }
+    }
+
+    fn positions(system: &ParticleSystem) -> Vec<(f32, f32)> {
+        system
+            .particles
+            .iter()
+            .filter(|particle| particle.alive)
+            .map(|particle| (particle.x, particle.y))
+            .collect()
+    }
+
+    #[test]
+    fn particles_die_at_the_end_of_their_lifetime() {
+        let mut system = ParticleSystem::new(16, 1);
+        let spark = system.add_style(spark());
+        system.emit(spark, 100, 100, 5);
+
+        (0..9).for_each(|_| system.update());
+        assert_eq!(system.live_count(), 5);
+
+        system.update();
+        assert_eq!(system.live_count(), 0);
+    }
+
+    #[test]
+    fn particles_fall_under_gravity() {
+        let mut system = ParticleSystem::new(1, 1);
+        let spark = system.add_style(ParticleStyle {
+            velocity_x: 0.0..=0.0,
+            velocity_y: 0.0..=0.0,
+            ..spark()
+        });
+        system.emit(spark, 0, 0, 1);
+
+        (0..4).for_each(|_| system.update());
+
+        assert_eq!(positions(&system), vec![(0.0, 5.0)]);
+    }
+
+    #[test]
+    fn the_pool_never_grows_past_its_budget() {
+        let mut system = ParticleSystem::new(8, 1);
+        let spark = system.add_style(spark());
+
+        system.emit(spark, 0, 0, 5);
+        system.emit(spark, 0, 0, 5);
+
+        assert_eq!(system.live_count(), 8);
+        assert_eq!(system.particles.len(), 8);
+    }
+
+    #[test]
+    fn clearing_kills_every_particle() {
+        let mut system = ParticleSystem::new(8, 1);
+        let spark = system.add_style(spark());
+        system.emit(spark, 0, 0, 8);
+
+        system.clear();
+
+        assert_eq!(system.live_count(), 0);
+    }
+
+    #[test]
+    fn dead_particles_are_reused() {
+        let mut system = ParticleSystem::new(4, 1);
+        let spark = system.add_style(spark());
+        system.emit(spark, 0, 0, 4);
+        (0..10).for_each(|_| system.update());
+
+        system.emit(spark, 0, 0, 4);
+
+        assert_eq!(system.live_count(), 4);
+    }
+
+    #[test]
+    fn the_same_seed_sends_particles_the_same_way() {
+        let run = || {
+            let mut system = ParticleSystem::new(8, 3);
+            let spark = system.add_style(spark());
+            system.emit(spark, 50, 50, 8);
+            (0..5).for_each(|_| system.update());
+            positions(&system)
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn colour_fades_from_start_to_end_over_a_lifetime() {
+        let style = CompiledStyle::new(spark());
+        let young = Particle {
+            alive: true,
+            ..Particle::default()
+        };
+        let old = Particle { age: 9, ..young };
+
+        assert_eq!(style.shades[style.shade(&young)], spark().start_color);
+        assert!(style.shades[style.shade(&old)].alpha < 0.2);
+    }
+
+    #[test]
+    fn sprite_frames_play_out_over_a_lifetime() {
+        let style = CompiledStyle::new(ParticleStyle {
+            frames: vec!["a".into(), "b".into()],
+            ..spark()
+        });
+        let particle = |age| Particle {
+            age,
+            ..Particle::default()
+        };
+
+        assert_eq!(style.frame(&particle(0)), Some("a"));
+        assert_eq!(style.frame(&particle(4)), Some("a"));
+        assert_eq!(style.frame(&particle(5)), Some("b"));
+        assert_eq!(style.frame(&particle(9)), Some("b"));
+    }
+
+    #[test]
+    fn continuous_emitters_stream_fractions_of_a_particle() {
+        let mut system = ParticleSystem::new(32, 1);
+        let spark = system.add_style(ParticleStyle {
+            lifetime: 100,
+            ..spark()
+        });
+        let mut emitter = Emitter::new(spark, Emission::Continuous(0.25));
+
+        (0..8).for_each(|_| emitter.emit(&mut system, 0, 0));
+
+        assert_eq!(system.live_count(), 2);
+    }
+
+    #[test]
+    fn bursts_go_off_in_full_each_time() {
+        let mut system = ParticleSystem::new(32, 1);
+        let spark = system.add_style(spark());
+        let mut emitter = Emitter::new(spark, Emission::Burst(6));
+
+        emitter.emit(&mut system, 0, 0);
+        emitter.emit(&mut system, 0, 0);
+
+        assert_eq!(system.live_count(), 12);
+    }
+}
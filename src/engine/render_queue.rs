@@ -0,0 +1,147 @@
+use crate::engine::camera::Camera;
+use crate::engine::{Point, Renderer};
+
+/// The broad bands a frame is drawn in, back to front.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Background,
+    World,
+    /// Scenery the boy runs behind.
+    #[allow(dead_code)]
+    Foreground,
+    /// Covers the whole screen, such as a flash.
+    Overlay,
+    Hud,
+}
+
+struct Command<'a> {
+    layer: Layer,
+    z: i16,
+    offset: Point,
+    draw: Box<dyn FnOnce(&Renderer) + 'a>,
+}
+
+/// Draws held back until `flush`, then drawn by layer and then by z.
+pub struct RenderQueue<'a> {
+    offset: Point,
+    commands: Vec<Command<'a>>,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> Self {
+        RenderQueue {
+            offset: Point::default(),
+            commands: vec![],
+        }
+    }
+
+    /// Moves everything `submit` submits back by `offset`.
+    pub fn with_offset(&mut self, offset: Point, submit: impl FnOnce(&mut Self)) {
+        let previous = self.offset;
+        self.offset = Point {
+            x: previous.x + offset.x,
+            y: previous.y + offset.y,
+        };
+        submit(self);
+        self.offset = previous;
+    }
+
+    /// Draws everything `submit` submits as seen through `camera`.
+    pub fn with_camera(&mut self, camera: &Camera, submit: impl FnOnce(&mut Self)) {
+        self.with_offset(camera.position(), submit);
+    }
+
+    /// Higher `z` draws over lower within the same layer.
+    pub fn submit(&mut self, layer: Layer, z: i16, draw: impl FnOnce(&Renderer) + 'a) {
+        self.commands.push(Command {
+            layer,
+            z,
+            offset: self.offset,
+            draw: Box::new(draw),
+        });
+    }
+
+    fn sorted(mut self) -> Vec<Command<'a>> {
+        // A stable sort, so ties stay in submission order.
+        self.commands
+            .sort_by_key(|command| (command.layer, command.z));
+        self.commands
+    }
+
+    pub fn flush(self, renderer: &Renderer) {
+        self.sorted().into_iter().for_each(|command| {
+            renderer.with_offset(command.offset, command.draw);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(queue: RenderQueue) -> Vec<(Layer, i16, i16)> {
+        queue
+            .sorted()
+            .iter()
+            .map(|command| (command.layer, command.z, command.offset.x))
+            .collect()
+    }
+
+    #[test]
+    fn draws_are_ordered_by_layer_then_z() {
+        let mut queue = RenderQueue::new();
+        queue.submit(Layer::Hud, 0, |_| {});
+        queue.submit(Layer::Overlay, -3, |_| {});
+        queue.submit(Layer::World, 2, |_| {});
+        queue.submit(Layer::Foreground, -10, |_| {});
+        queue.submit(Layer::Background, 5, |_| {});
+        queue.submit(Layer::World, -1, |_| {});
+
+        assert_eq!(
+            order(queue),
+            vec![
+                (Layer::Background, 5, 0),
+                (Layer::World, -1, 0),
+                (Layer::World, 2, 0),
+                (Layer::Foreground, -10, 0),
+                (Layer::Overlay, -3, 0),
+                (Layer::Hud, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn ties_keep_submission_order() {
+        let mut queue = RenderQueue::new();
+        (1..=3).for_each(|x| {
+            queue.with_offset(Point { x, y: 0 }, |queue| {
+                queue.submit(Layer::World, 0, |_| {});
+            })
+        });
+
+        let offsets: Vec<i16> = order(queue).into_iter().map(|(_, _, x)| x).collect();
+
+        assert_eq!(offsets, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn draws_keep_the_offset_they_were_submitted_with() {
+        let mut queue = RenderQueue::new();
+        queue.with_offset(Point { x: 3, y: 0 }, |queue| {
+            queue.with_offset(Point { x: 100, y: 0 }, |queue| {
+                queue.submit(Layer::World, 0, |_| {});
+            });
+            queue.submit(Layer::Background, 0, |_| {});
+        });
+        queue.submit(Layer::Hud, 0, |_| {});
+
+        assert_eq!(
+            order(queue),
+            vec![
+                (Layer::Background, 0, 3),
+                (Layer::World, 0, 103),
+                (Layer::Hud, 0, 0),
+            ]
+        );
+    }
+}
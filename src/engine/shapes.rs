@@ -0,0 +1,76 @@
+use crate::engine::{Color, Rect};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stroke {
+    pub color: Color,
+    pub width: f32,
+}
+
+/// The outline is drawn over the fill.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShapeStyle {
+    pub fill: Option<Color>,
+    pub stroke: Option<Stroke>,
+}
+
+impl ShapeStyle {
+    pub const fn filled(color: Color) -> Self {
+        ShapeStyle {
+            fill: Some(color),
+            stroke: None,
+        }
+    }
+
+    pub const fn stroked(color: Color, width: f32) -> Self {
+        ShapeStyle {
+            fill: None,
+            stroke: Some(Stroke { color, width }),
+        }
+    }
+
+    pub const fn with_stroke(self, color: Color, width: f32) -> Self {
+        ShapeStyle {
+            stroke: Some(Stroke { color, width }),
+            ..self
+        }
+    }
+}
+
+pub const DEBUG_OUTLINE: ShapeStyle = ShapeStyle::stroked(Color::rgba(255, 0, 0, 1.0), 1.0);
+
+/// Corners can't round further than halfway across `rect`.
+pub fn corner_radius(rect: &Rect, radius: i16) -> i16 {
+    radius.min(rect.w / 2).min(rect.h / 2).max(0)
+}
+
+/// The part of `area` filled to show `progress`, from 0 to 1.
+pub fn bar_fill(area: &Rect, progress: f32) -> Rect {
+    let width = (f32::from(area.w) * progress.clamp(0.0, 1.0)).round() as i16;
+    Rect::new(area.position, width, area.h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corners_round_at_most_halfway_across() {
+        let rect = Rect::new_from_x_y(0, 0, 100, 20);
+
+        assert_eq!(corner_radius(&rect, 6), 6);
+        assert_eq!(corner_radius(&rect, 30), 10);
+        assert_eq!(corner_radius(&rect, -4), 0);
+    }
+
+    #[test]
+    fn bars_fill_from_the_left_by_their_progress() {
+        let area = Rect::new_from_x_y(10, 20, 90, 12);
+
+        assert_eq!(
+            bar_fill(&area, 1.0 / 3.0),
+            Rect::new_from_x_y(10, 20, 30, 12)
+        );
+        assert_eq!(bar_fill(&area, 1.5), area);
+        assert_eq!(bar_fill(&area, -1.0).w, 0);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::engine::{Color, Point, Rect};
+use anyhow::Result;
+
+/// The font bundled with the game, loaded by style.css.
+pub const DEFAULT_FONT_FAMILY: &str = "'Ken Future', serif";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    pub fn css(&self) -> &'static str {
+        match self {
+            Align::Left => "left",
+            Align::Center => "center",
+            Align::Right => "right",
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Baseline {
+    Top,
+    Middle,
+    #[default]
+    Alphabetic,
+    Bottom,
+}
+
+impl Baseline {
+    pub fn css(&self) -> &'static str {
+        match self {
+            Baseline::Top => "top",
+            Baseline::Middle => "middle",
+            Baseline::Alphabetic => "alphabetic",
+            Baseline::Bottom => "bottom",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outline {
+    pub color: Color,
+    pub width: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shadow {
+    pub color: Color,
+    pub offset: Point,
+    pub blur: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextStyle {
+    pub family: &'static str,
+    /// In pixels.
+    pub size: u16,
+    pub color: Color,
+    pub align: Align,
+    pub baseline: Baseline,
+    pub outline: Option<Outline>,
+    pub shadow: Option<Shadow>,
+    /// How far apart wrapped lines are, as a multiple of `size`.
+    pub line_height: f32,
+}
+
+impl TextStyle {
+    /// Black text in the bundled font.
+    pub const DEFAULT: TextStyle = TextStyle {
+        family: DEFAULT_FONT_FAMILY,
+        size: 16,
+        color: Color::rgba(0, 0, 0, 1.0),
+        align: Align::Left,
+        baseline: Baseline::Alphabetic,
+        outline: None,
+        shadow: None,
+        line_height: 1.2,
+    };
+
+    /// The style as the canvas takes it for `font`.
+    pub fn font(&self) -> String {
+        format!("{}px {}", self.size, self.family)
+    }
+
+    fn line_step(&self) -> i16 {
+        (f32::from(self.size) * self.line_height).round() as i16
+    }
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle::DEFAULT
+    }
+}
+
+/// Breaks `text` between words into lines no wider than `max_width`.
+pub fn wrap(
+    text: &str,
+    max_width: f64,
+    mut measure: impl FnMut(&str) -> Result<f64>,
+) -> Result<Vec<String>> {
+    let mut lines = vec![];
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let longer = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if line.is_empty() || measure(&longer)? <= max_width {
+                line = longer;
+            } else {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            }
+        }
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+/// Where each wrapped line goes, for drawing with the top baseline.
+pub fn layout(
+    text: &str,
+    area: &Rect,
+    style: &TextStyle,
+    measure: impl FnMut(&str) -> Result<f64>,
+) -> Result<Vec<(String, Point)>> {
+    let x = match style.align {
+        Align::Left => area.x(),
+        Align::Center => area.x() + area.w / 2,
+        Align::Right => area.right(),
+    };
+    Ok(wrap(text, area.w.into(), measure)?
+        .into_iter()
+        .enumerate()
+        .map(|(row, line)| {
+            let y = area.y() + row as i16 * style.line_step();
+            (line, Point { x, y })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every character is ten pixels wide.
+    fn measure(text: &str) -> Result<f64> {
+        Ok(text.chars().count() as f64 * 10.0)
+    }
+
+    #[test]
+    fn styles_name_their_font_for_the_canvas() {
+        let style = TextStyle {
+            size: 20,
+            ..TextStyle::DEFAULT
+        };
+
+        assert_eq!(style.font(), "20px 'Ken Future', serif");
+    }
+
+    #[test]
+    fn baselines_are_named_for_the_canvas() {
+        assert_eq!(Baseline::Top.css(), "top");
+        assert_eq!(Baseline::Middle.css(), "middle");
+        assert_eq!(Baseline::Alphabetic.css(), "alphabetic");
+        assert_eq!(Baseline::Bottom.css(), "bottom");
+    }
+
+    #[test]
+    fn text_wraps_between_words() {
+        let lines = wrap("the quick brown fox", 100.0, measure).unwrap();
+
+        assert_eq!(lines, vec!["the quick", "brown fox"]);
+    }
+
+    #[test]
+    fn line_breaks_in_the_text_are_kept() {
+        let lines = wrap("game\n\nover", 100.0, measure).unwrap();
+
+        assert_eq!(lines, vec!["game", "", "over"]);
+    }
+
+    #[test]
+    fn words_too_wide_for_a_line_get_one_anyway() {
+        let lines = wrap("a extraordinarily b", 50.0, measure).unwrap();
+
+        assert_eq!(lines, vec!["a", "extraordinarily", "b"]);
+    }
+
+    #[test]
+    fn lines_are_laid_out_down_the_area_from_its_aligned_side() {
+        let area = Rect::new_from_x_y(100, 50, 100, 200);
+        let style = TextStyle {
+            size: 20,
+            align: Align::Center,
+            ..TextStyle::DEFAULT
+        };
+
+        let lines = layout("the quick brown fox", &area, &style, measure).unwrap();
+
+        assert_eq!(
+            lines,
+            vec![
+                ("the quick".to_string(), Point { x: 150, y: 50 }),
+                ("brown fox".to_string(), Point { x: 150, y: 74 }),
+            ]
+        );
+    }
+}
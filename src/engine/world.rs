@@ -0,0 +1,412 @@
+use crate::engine::particles::{Emitter, ParticleSystem};
+use crate::engine::shapes::DEBUG_OUTLINE;
+use crate::engine::{DrawParams, Point, Rect, Renderer, SpriteSheet};
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use web_sys::HtmlImageElement;
+
+/// Entities are plain ids. They are handed out in increasing order, so
+/// iterating a component store visits entities in the order they were
+/// spawned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Entity(u32);
+
+pub struct Components<C> {
+    items: BTreeMap<Entity, C>,
+}
+
+impl<C> Components<C> {
+    fn new() -> Self {
+        Components {
+            items: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, component: C) {
+        self.items.insert(entity, component);
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&C> {
+        self.items.get(&entity)
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut C> {
+        self.items.get_mut(&entity)
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<C> {
+        self.items.remove(&entity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &C)> {
+        self.items
+            .iter()
+            .map(|(entity, component)| (*entity, component))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut C)> {
+        self.items
+            .iter_mut()
+            .map(|(entity, component)| (*entity, component))
+    }
+}
+
+pub enum Sprite {
+    #[allow(dead_code)]
+    Image(HtmlImageElement),
+    Cell {
+        sheet: Rc<SpriteSheet>,
+        name: String,
+    },
+}
+
+impl Sprite {
+    fn draw(&self, renderer: &Renderer, position: Point, params: &DrawParams) -> Result<()> {
+        match self {
+            Sprite::Image(image) => {
+                let (w, h) = (image.width() as i16, image.height() as i16);
+                renderer.draw_image_with(
+                    image,
+                    &Rect::new_from_x_y(0, 0, w, h),
+                    &Rect::new(position, w, h),
+                    params,
+                )
+            }
+            Sprite::Cell { sheet, name } => {
+                let cell = sheet
+                    .cell(name)
+                    .ok_or_else(|| anyhow!("Sprite {} not found", name))?;
+                sheet.draw_with(
+                    renderer,
+                    &Rect::new_from_x_y(cell.frame.x, cell.frame.y, cell.frame.w, cell.frame.h),
+                    &Rect::new_from_x_y(position.x, position.y, cell.frame.w, cell.frame.h),
+                    params,
+                )
+            }
+        }
+    }
+
+    pub fn width(&self) -> i16 {
+        match self {
+            Sprite::Image(image) => image.width() as i16,
+            Sprite::Cell { sheet, name } => sheet.cell(name).map_or(0, |cell| cell.frame.w),
+        }
+    }
+}
+
+/// Component storage for everything that moves through the game. `T` is the
+/// game's tag type, which says what an entity is and carries any data that
+/// only matters to the systems for that kind of entity.
+pub struct World<T> {
+    next_entity: u32,
+    pub positions: Components<Point>,
+    pub velocities: Components<Point>,
+    pub sprites: Components<Sprite>,
+    /// How sprites are drawn, for the ones not drawn plainly.
+    pub params: Components<DrawParams>,
+    /// Collision boxes, relative to the entity's position.
+    pub colliders: Components<Rect>,
+    /// Emitters make their particles at the entity's position.
+    pub emitters: Components<Emitter>,
+    pub tags: Components<T>,
+    /// Shared by every emitter.
+    pub particles: ParticleSystem,
+}
+
+impl<T> World<T> {
+    pub fn new() -> Self {
+        World {
+            next_entity: 0,
+            positions: Components::new(),
+            velocities: Components::new(),
+            sprites: Components::new(),
+            params: Components::new(),
+            colliders: Components::new(),
+            emitters: Components::new(),
+            tags: Components::new(),
+            particles: ParticleSystem::new(0, 0),
+        }
+    }
+
+    pub fn with_particles(mut self, particles: ParticleSystem) -> Self {
+        self.particles = particles;
+        self
+    }
+
+    pub fn spawn(&mut self, tag: T) -> Entity {
+        let entity = Entity(self.next_entity);
+        self.next_entity += 1;
+        self.tags.insert(entity, tag);
+        entity
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.positions.remove(entity);
+        self.velocities.remove(entity);
+        self.sprites.remove(entity);
+        self.params.remove(entity);
+        self.colliders.remove(entity);
+        self.emitters.remove(entity);
+        self.tags.remove(entity);
+    }
+
+    pub fn tagged(&self, predicate: impl Fn(&T) -> bool) -> Vec<Entity> {
+        self.tags
+            .iter()
+            .filter(|(_, tag)| predicate(tag))
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    /// The entity's collider in world coordinates.
+    pub fn bounding_box(&self, entity: Entity) -> Option<Rect> {
+        let position = self.positions.get(entity)?;
+        let collider = self.colliders.get(entity)?;
+        Some(Rect::new_from_x_y(
+            position.x + collider.x(),
+            position.y + collider.y(),
+            collider.w,
+            collider.h,
+        ))
+    }
+
+    pub fn colliding_with(&self, rect: &Rect) -> Vec<Entity> {
+        self.colliders
+            .iter()
+            .filter_map(|(entity, _)| {
+                self.bounding_box(entity)
+                    .filter(|bounding_box| bounding_box.interests(rect))
+                    .map(|_| entity)
+            })
+            .collect()
+    }
+
+    pub fn emit(&mut self, entity: Entity) {
+        let World {
+            positions,
+            emitters,
+            particles,
+            ..
+        } = self;
+        if let (Some(position), Some(emitter)) = (positions.get(entity), emitters.get_mut(entity)) {
+            emitter.emit(particles, position.x, position.y);
+        }
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        let plain = DrawParams::default();
+        self.sprites.iter().for_each(|(entity, sprite)| {
+            if let Some(position) = self.positions.get(entity) {
+                let params = self.params.get(entity).unwrap_or(&plain);
+                if let Err(err) = sprite.draw(renderer, *position, params) {
+                    error!("Could not draw entity {:?} {:#?}", entity, err);
+                }
+            }
+        });
+    }
+
+    pub fn draw_colliders(&self, renderer: &Renderer) {
+        self.colliders.iter().for_each(|(entity, _)| {
+            if let Some(bounding_box) = self.bounding_box(entity) {
+                renderer.draw_rect(&bounding_box, &DEBUG_OUTLINE);
+            }
+        });
+    }
+
+    pub fn draw_particles(&self, renderer: &Renderer) {
+        self.particles.draw(renderer);
+    }
+}
+
+pub type System<T> = fn(&mut World<T>);
+
+/// Systems run in the order they were added.
+pub struct Systems<T> {
+    systems: Vec<System<T>>,
+}
+
+impl<T> Systems<T> {
+    pub fn new() -> Self {
+        Systems { systems: vec![] }
+    }
+
+    pub fn with(mut self, system: System<T>) -> Self {
+        self.systems.push(system);
+        self
+    }
+
+    pub fn run(&self, world: &mut World<T>) {
+        self.systems.iter().for_each(|system| system(world));
+    }
+}
+
+pub fn movement<T>(world: &mut World<T>) {
+    let World {
+        positions,
+        velocities,
+        ..
+    } = world;
+    velocities.iter().for_each(|(entity, velocity)| {
+        if let Some(position) = positions.get_mut(entity) {
+            position.x += velocity.x;
+            position.y += velocity.y;
+        }
+    });
+}
+
+pub fn update_particles<T>(world: &mut World<T>) {
+    world.particles.update();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::particles::{Emission, ParticleStyle, StyleId};
+    use crate::engine::Color;
+
+    fn spark(particles: &mut ParticleSystem) -> StyleId {
+        particles.add_style(ParticleStyle {
+            lifetime: 2,
+            velocity_x: 0.0..=0.0,
+            velocity_y: 0.0..=0.0,
+            gravity: 0.0,
+            size: 2,
+            start_color: Color::rgba(255, 255, 255, 1.0),
+            end_color: Color::rgba(255, 255, 255, 0.0),
+            frames: vec![],
+        })
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Tag {
+        Coin,
+        Enemy,
+    }
+
+    #[test]
+    fn spawned_entities_get_increasing_ids() {
+        let mut world = World::new();
+
+        let first = world.spawn(Tag::Coin);
+        let second = world.spawn(Tag::Enemy);
+
+        assert!(first < second);
+        assert_eq!(world.tags.get(second), Some(&Tag::Enemy));
+    }
+
+    #[test]
+    fn despawn_removes_every_component() {
+        let mut world = World::new();
+        let entity = world.spawn(Tag::Coin);
+        world.positions.insert(entity, Point { x: 1, y: 2 });
+        world.velocities.insert(entity, Point { x: -1, y: 0 });
+        world
+            .colliders
+            .insert(entity, Rect::new_from_x_y(0, 0, 10, 10));
+        world.params.insert(entity, DrawParams::default());
+        let style = spark(&mut world.particles);
+        world
+            .emitters
+            .insert(entity, Emitter::new(style, Emission::Burst(1)));
+
+        world.despawn(entity);
+
+        assert!(world.positions.get(entity).is_none());
+        assert!(world.velocities.get(entity).is_none());
+        assert!(world.colliders.get(entity).is_none());
+        assert!(world.params.get(entity).is_none());
+        assert!(world.emitters.get(entity).is_none());
+        assert!(world.tags.get(entity).is_none());
+    }
+
+    #[test]
+    fn emitters_fill_the_world_particle_pool() {
+        let mut particles = ParticleSystem::new(8, 1);
+        let spark = spark(&mut particles);
+        let mut world = World::new().with_particles(particles);
+        let emitter = world.spawn(Tag::Enemy);
+        world.positions.insert(emitter, Point { x: 5, y: 5 });
+        world
+            .emitters
+            .insert(emitter, Emitter::new(spark, Emission::Burst(3)));
+        let silent = world.spawn(Tag::Coin);
+
+        world.emit(emitter);
+        world.emit(silent);
+        assert_eq!(world.particles.live_count(), 3);
+
+        Systems::new()
+            .with(update_particles)
+            .with(update_particles)
+            .run(&mut world);
+        assert_eq!(world.particles.live_count(), 0);
+    }
+
+    #[test]
+    fn movement_applies_velocity_to_position() {
+        let mut world = World::new();
+        let moving = world.spawn(Tag::Enemy);
+        world.positions.insert(moving, Point { x: 10, y: 10 });
+        world.velocities.insert(moving, Point { x: -4, y: 2 });
+        let still = world.spawn(Tag::Coin);
+        world.positions.insert(still, Point { x: 10, y: 10 });
+
+        movement(&mut world);
+
+        assert_eq!(
+            world.positions.get(moving).map(|p| (p.x, p.y)),
+            Some((6, 12))
+        );
+        assert_eq!(
+            world.positions.get(still).map(|p| (p.x, p.y)),
+            Some((10, 10))
+        );
+    }
+
+    #[test]
+    fn systems_run_in_the_order_they_were_added() {
+        fn double_x(world: &mut World<Tag>) {
+            world
+                .positions
+                .iter_mut()
+                .for_each(|(_, position)| position.x *= 2);
+        }
+        let mut world = World::new();
+        let entity = world.spawn(Tag::Enemy);
+        world.positions.insert(entity, Point { x: 1, y: 0 });
+        world.velocities.insert(entity, Point { x: 1, y: 0 });
+
+        Systems::new().with(movement).with(double_x).run(&mut world);
+
+        assert_eq!(world.positions.get(entity).map(|p| p.x), Some(4));
+    }
+
+    #[test]
+    fn colliding_with_uses_colliders_relative_to_position() {
+        let mut world = World::new();
+        let near = world.spawn(Tag::Coin);
+        world.positions.insert(near, Point { x: 100, y: 100 });
+        world
+            .colliders
+            .insert(near, Rect::new_from_x_y(5, 5, 10, 10));
+        let far = world.spawn(Tag::Coin);
+        world.positions.insert(far, Point { x: 300, y: 100 });
+        world
+            .colliders
+            .insert(far, Rect::new_from_x_y(0, 0, 10, 10));
+
+        let hits = world.colliding_with(&Rect::new_from_x_y(110, 110, 20, 20));
+
+        assert_eq!(hits, vec![near]);
+    }
+
+    #[test]
+    fn tagged_filters_by_tag() {
+        let mut world = World::new();
+        world.spawn(Tag::Coin);
+        let enemy = world.spawn(Tag::Enemy);
+
+        assert_eq!(world.tagged(|tag| *tag == Tag::Enemy), vec![enemy]);
+    }
+}
@@ -1,22 +1,103 @@
 use crate::browser;
+use crate::difficulty::Difficulty;
 use crate::engine;
+use crate::engine::bitmap_font::BitmapFont;
+use crate::engine::camera::Camera;
+use crate::engine::effects::{Effects, Flash};
+use crate::engine::parallax::{ParallaxBackground, ParallaxDefinition};
+use crate::engine::particles::{Emission, Emitter, ParticleStyle, ParticleSystem};
+use crate::engine::render_queue::{Layer, RenderQueue};
+use crate::engine::shapes::{self, ShapeStyle, DEBUG_OUTLINE};
+use crate::engine::text::{Align, Outline, Shadow, TextStyle};
+use crate::engine::world::{movement, update_particles, Entity, Sprite, Systems, World};
 use crate::engine::{
-    Audio, Cell, Game, Image, KeyState, Point, Rect, Renderer, Sheet, Sound, SpriteSheet,
+    Audio, Cell, Color, DrawParams, Game, Image, KeyState, Point, Rect, Renderer, Sheet, Sound,
+    SpriteSheet,
 };
+use crate::game::dog::Dog;
 use crate::game::red_hat_boy_states::*;
-use crate::segment::{platform_and_stone, stone_and_platform};
+use crate::movement::Movement;
+use crate::score::{HighScores, Score};
+use crate::segment::{SegmentAssets, SegmentLibrary};
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures::channel::mpsc::UnboundedReceiver;
 use rand::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::rc::Rc;
 use web_sys::HtmlImageElement;
 
+mod dog;
+pub mod simulation;
+
+const WIDTH: i16 = 600;
 const HEIGHT: i16 = 600;
+const GROUND_DEPTH: i16 = 80;
+const GROUND_SPRITE: &str = "2.png";
+pub(crate) const PIT_SPRITE: &str = "18.png";
+const SCREEN: Rect = Rect::new_from_x_y(0, 0, WIDTH, HEIGHT + GROUND_DEPTH);
 
 const TIMELINE_MINIMUM: i16 = 1000;
-
-const OBSTACLE_BUFFER: i16 = 20;
+const BOY_SCREEN_X: i16 = 70;
+const CAMERA_LOOK_AHEAD: i16 = WIDTH / 2 - BOY_SCREEN_X;
+const CAMERA_DEAD_ZONE_TOP: i16 = 100;
+const CAMERA_DEAD_ZONE_BOTTOM: i16 = 400;
+// Keeps i16 positions from overflowing.
+const RECENTER_DISTANCE: i16 = 10_000;
+const STARTING_SEGMENT_OFFSET: i16 = 90;
+
+const BLINK_FRAMES: u8 = 4;
+const ONE_WAY_TOLERANCE: i16 = 10;
+const CRUMBLE_GRAVITY: i16 = 1;
+const CRUMBLE_TERMINAL_VELOCITY: i16 = 20;
+const THROW_HEIGHT: i16 = 20;
+const PICKUP_SPIN_FRAMES: u16 = 90;
+
+const HIT_TRAUMA: f32 = 0.6;
+const HIT_STOP_FRAMES: u8 = 6;
+const HIT_FLASH: Flash = Flash {
+    color: Color::rgba(255, 80, 80, 0.5),
+    frames: 12,
+};
+const FALL_TRAUMA: f32 = 0.8;
+const PARTICLE_BUDGET: usize = 256;
+const DUST_PER_FRAME: f32 = 0.3;
+const LANDING_PUFFS: u16 = 8;
+const IMPACT_SPARKS: u16 = 24;
+
+const GROUND_Z: i16 = 0;
+const OBSTACLE_Z: i16 = 1;
+const DOG_Z: i16 = 2;
+const BOY_Z: i16 = 3;
+const PARTICLE_Z: i16 = 4;
+
+const HUD_POSITION: Point = Point { x: 10, y: 12 };
+const HUD_PANEL: ShapeStyle = ShapeStyle::filled(Color::rgba(20, 20, 30, 0.35))
+    .with_stroke(Color::rgba(255, 255, 255, 0.4), 2.0);
+const HUD_PANEL_PADDING: i16 = 6;
+const HUD_PANEL_RADIUS: i16 = 8;
+const HEALTH_BAR_WIDTH: i16 = 100;
+const HEALTH_BAR_HEIGHT: i16 = 17;
+const HEALTH_BAR_BACK: ShapeStyle = ShapeStyle::filled(Color::rgba(60, 20, 20, 1.0));
+const HEALTH_BAR_FILL: ShapeStyle = ShapeStyle::filled(Color::rgba(220, 50, 50, 1.0));
+const HEALTH_BAR_EDGE: ShapeStyle = ShapeStyle::stroked(Color::rgba(40, 30, 30, 1.0), 3.0);
+const FINAL_SCORE_TEXT: TextStyle = TextStyle {
+    size: 32,
+    color: Color::rgba(255, 255, 255, 1.0),
+    align: Align::Center,
+    outline: Some(Outline {
+        color: Color::rgba(40, 30, 30, 1.0),
+        width: 4.0,
+    }),
+    shadow: Some(Shadow {
+        color: Color::rgba(0, 0, 0, 0.5),
+        offset: Point { x: 3, y: 3 },
+        blur: 4.0,
+    }),
+    ..TextStyle::DEFAULT
+};
+const FINAL_SCORE_BOX: Rect = Rect::new_from_x_y(20, 100, WIDTH - 40, 120);
 
 pub struct WalkTheDog {
     machine: Option<WalkTheDogStateMachine>,
@@ -38,42 +119,100 @@ impl Game for WalkTheDog {
                 let jump_sound = audio.load_sound("SFX_Jump_23.mp3").await?;
                 let background_music = audio.load_sound("background_song.mp3").await?;
                 audio.play_looping_sound(&background_music)?;
+                let difficulty = browser::fetch_json("difficulty.json")
+                    .await?
+                    .into_serde::<Difficulty>()?
+                    .validate()?;
+                let movement = browser::fetch_json("movement.json")
+                    .await?
+                    .into_serde::<Movement>()?;
+                let bark_sound = audio.load_sound("SFX_Bark.wav").await?;
+                let pickup_sound = audio.load_sound("SFX_Pickup.wav").await?;
+                let dog = Dog::new(
+                    browser::fetch_json("dog.json").await?.into_serde()?,
+                    engine::load_image("dog.png").await?,
+                    audio.clone(),
+                    bark_sound,
+                );
                 let rhb = RedHatBoy::new(
                     browser::fetch_json("rhb.json").await?.into_serde()?,
                     engine::load_image("rhb.png").await?,
-                    audio,
+                    audio.clone(),
                     jump_sound,
+                    difficulty.lives(),
+                    movement,
                 );
-                let background = engine::load_image("BG.png").await?;
-                let stone = engine::load_image("Stone.png").await?;
-                let tiles = browser::fetch_json("tiles.json").await?;
-                let sprite_sheet = Rc::new(SpriteSheet::new(
-                    tiles.into_serde()?,
+                let background = ParallaxBackground::load(
+                    &browser::fetch_json("background.json")
+                        .await?
+                        .into_serde::<ParallaxDefinition>()?,
+                    WIDTH,
+                )
+                .await?;
+                let tiles = Rc::new(SpriteSheet::new(
+                    browser::fetch_json("tiles.json").await?.into_serde()?,
                     engine::load_image("tiles.png").await?,
                 ));
+                let pickups = Rc::new(SpriteSheet::new(
+                    browser::fetch_json("pickups.json").await?.into_serde()?,
+                    engine::load_image("pickups.png").await?,
+                ));
+                let enemies = Rc::new(SpriteSheet::new(
+                    browser::fetch_json("enemies.json").await?.into_serde()?,
+                    engine::load_image("enemies.png").await?,
+                ));
+                let particle_sheet = Rc::new(SpriteSheet::new(
+                    browser::fetch_json("particles.json").await?.into_serde()?,
+                    engine::load_image("particles.png").await?,
+                ));
+                let font = BitmapFont::new(
+                    &browser::fetch_json("font.json").await?.into_serde()?,
+                    engine::load_image("font.png").await?,
+                );
 
-                let background_width = background.width() as i16;
-
-                let starting_obstacles = stone_and_platform(stone.clone(), sprite_sheet.clone(), 0);
-                let timeline = rightmost(&starting_obstacles);
+                let segments = browser::fetch_json("segments.json")
+                    .await?
+                    .into_serde::<SegmentLibrary>()?
+                    .validate()?;
+                segments.ensure_segments_exist(&difficulty)?;
+                segments.ensure_sprites_exist(&tiles, &pickups, &enemies)?;
+                let mut images = HashMap::new();
+                for image_name in segments.image_names() {
+                    images.insert(image_name.into(), engine::load_image(image_name).await?);
+                }
+
+                let mut world = World::new().with_particles(
+                    ParticleSystem::new(PARTICLE_BUDGET, thread_rng().gen())
+                        .with_sheet(particle_sheet),
+                );
+                spawn_emitters(&mut world);
+                spawn_ground(&mut world, tiles.clone());
 
-                let machine = WalkTheDogStateMachine::new(Walk {
+                let mut walk = Walk {
                     boy: rhb,
-                    backgrounds: [
-                        Image::new(background.clone(), Point { x: 0, y: 0 }),
-                        Image::new(
-                            background,
-                            Point {
-                                x: background_width,
-                                y: 0,
-                            },
-                        ),
-                    ],
-                    obstacles: starting_obstacles,
-                    obstacle_sheet: sprite_sheet,
-                    stone,
-                    timeline,
-                });
+                    dog,
+                    camera: new_camera(),
+                    effects: Effects::new(thread_rng().gen()),
+                    font,
+                    background,
+                    world,
+                    systems: walk_systems(),
+                    obstacles: vec![],
+                    segment_assets: SegmentAssets {
+                        images,
+                        tiles,
+                        pickups,
+                        enemies,
+                    },
+                    audio,
+                    pickup_sound,
+                    timeline: 0,
+                    score: Score::new(),
+                    difficulty,
+                    segments,
+                };
+                walk.place_starting_segment();
+                let machine = WalkTheDogStateMachine::new(walk);
 
                 Ok(Box::new(WalkTheDog {
                     machine: Some(machine),
@@ -91,10 +230,12 @@ impl Game for WalkTheDog {
     }
 
     fn draw(&self, renderer: &Renderer) {
-        renderer.clear(&Rect::new_from_x_y(0, 0, HEIGHT, HEIGHT));
+        renderer.clear(&SCREEN);
 
         if let Some(machine) = &self.machine {
-            machine.draw(renderer);
+            let mut queue = RenderQueue::new();
+            machine.draw(&mut queue);
+            queue.flush(renderer);
         }
     }
 }
@@ -118,11 +259,14 @@ impl WalkTheDogStateMachine {
         }
     }
 
-    fn draw(&self, renderer: &Renderer) {
+    fn draw<'a>(&'a self, queue: &mut RenderQueue<'a>) {
         match self {
-            WalkTheDogStateMachine::Ready(state) => state.draw(renderer),
-            WalkTheDogStateMachine::Walking(state) => state.draw(renderer),
-            WalkTheDogStateMachine::GameOver(state) => state.draw(renderer),
+            WalkTheDogStateMachine::Ready(state) => state.draw(queue),
+            WalkTheDogStateMachine::Walking(state) => state.draw(queue),
+            WalkTheDogStateMachine::GameOver(state) => {
+                state.draw(queue);
+                state._state.draw(queue);
+            }
         }
     }
 }
@@ -135,23 +279,28 @@ struct Ready;
 struct Walking;
 struct GameOver {
     new_game_event: UnboundedReceiver<()>,
+    score: Score,
 }
 enum ReadyEndState {
     Complete(WalkTheDogState<Walking>),
     Continue(WalkTheDogState<Ready>),
 }
+// Both variants carry the whole Walk, so boxing one wouldn't save anything.
+#[allow(clippy::large_enum_variant)]
 enum WalkingEndState {
     Complete(WalkTheDogState<GameOver>),
     Continue(WalkTheDogState<Walking>),
 }
+// Both variants carry the whole Walk, so boxing one wouldn't save anything.
+#[allow(clippy::large_enum_variant)]
 enum GameOverEndState {
     Complete(WalkTheDogState<Ready>),
     Continue(WalkTheDogState<GameOver>),
 }
 
 impl<T> WalkTheDogState<T> {
-    fn draw(&self, renderer: &Renderer) {
-        self.walk.draw(renderer);
+    fn draw<'a>(&'a self, queue: &mut RenderQueue<'a>) {
+        self.walk.draw(queue);
     }
 }
 
@@ -165,6 +314,7 @@ impl WalkTheDogState<Ready> {
 
     fn update(mut self, key_state: &KeyState) -> ReadyEndState {
         self.walk.boy.update();
+        self.walk.dog.update();
         if key_state.is_pressed("ArrowRight") {
             ReadyEndState::Complete(self.start_running())
         } else {
@@ -182,43 +332,88 @@ impl WalkTheDogState<Ready> {
 
     fn run_right(&mut self) {
         self.walk.boy.run_right();
+        self.walk.dog.run();
     }
 }
 
 impl WalkTheDogState<Walking> {
     fn update(mut self, key_state: &KeyState) -> WalkingEndState {
+        self.walk.effects.update();
+        if self.walk.effects.take_frozen_frame() {
+            return WalkingEndState::Continue(self);
+        }
+        let lives = self.walk.boy.lives();
+        let was_airborne = self.walk.boy.airborne();
+
         if key_state.is_pressed("ArrowDown") {
             self.walk.boy.slide();
         }
-        if key_state.is_pressed("Space") {
+        if key_state.just_pressed("Space") {
             self.walk.boy.jump();
         }
 
         self.walk.boy.update();
 
         let walking_speed = self.walk.velocity();
+        self.walk.boy.move_horizontally(walking_speed);
 
-        let [first_background, second_background] = &mut self.walk.backgrounds;
-        first_background.move_horizontally(walking_speed);
-        second_background.move_horizontally(walking_speed);
-        if first_background.right() < 0 {
-            first_background.set_x(second_background.right())
-        }
-        if second_background.right() < 0 {
-            second_background.set_x(first_background.right())
-        }
+        self.walk.follow_boy();
+        self.walk.systems.run(&mut self.walk.world);
+
+        self.walk.score.travel(walking_speed);
 
-        self.walk.obstacles.retain(|obstacle| obstacle.right() > 0);
+        let view = self.walk.camera.view();
+        let cleared = self
+            .walk
+            .obstacles
+            .iter()
+            .filter(|obstacle| obstacle.right() <= view.x() && obstacle.is_hazard())
+            .count();
+        self.walk
+            .obstacles
+            .retain(|obstacle| obstacle.right() > view.x());
+        self.walk.score.clear_obstacles(cleared);
+
+        self.walk
+            .obstacles
+            .iter_mut()
+            .for_each(|obstacle| obstacle.update(&view));
+        despawn_passed_pickups(&mut self.walk.world, view.x());
 
-        self.walk.obstacles.iter_mut().for_each(|obstacle| {
-            obstacle.move_horizontally(walking_speed);
-            obstacle.check_intersection(&mut self.walk.boy)
+        let Walk { obstacles, boy, .. } = &mut self.walk;
+        obstacles
+            .iter_mut()
+            .for_each(|obstacle| match obstacle.check_intersection(boy) {
+                Some(collision @ (Collision::Land(_) | Collision::Stomp)) => {
+                    obstacle.landed_on();
+                    boy.collide(collision);
+                }
+                Some(collision) => boy.collide(collision),
+                None => {}
+            });
+        self.walk.collect_pickups();
+        let impact = (self.walk.boy.lives() < lives).then(|| {
+            if self.walk.knocked_out() {
+                Impact::Fall
+            } else {
+                Impact::Hit
+            }
         });
+        if let Some(impact) = impact {
+            impact.apply(&mut self.walk.effects);
+        }
+        let landed = was_airborne && self.walk.boy.grounded();
+        emit_particles(&mut self.walk.world, &self.walk.boy, landed, impact);
 
-        if self.walk.timeline < TIMELINE_MINIMUM {
+        self.walk.dog.follow(&self.walk.boy, walking_speed);
+        self.walk.dog.watch(&self.walk.obstacles, &self.walk.camera);
+        self.walk.dog.update();
+
+        if self.walk.timeline - view.x() < TIMELINE_MINIMUM {
             self.walk.generate_next_segment()
-        } else {
-            self.walk.timeline += walking_speed;
+        }
+        if view.x() > RECENTER_DISTANCE {
+            self.walk.recenter();
         }
 
         if self.walk.knocked_out() {
@@ -229,14 +424,16 @@ impl WalkTheDogState<Walking> {
     }
 
     fn end_game(self) -> WalkTheDogState<GameOver> {
-        let receiver = browser::draw_ui("<button id='new_game'>New Game</button>")
+        let (high_scores, rank) = record_high_score(&self.walk.score);
+        let receiver = browser::draw_ui(&game_over_ui(&high_scores, rank))
             .and_then(|_unit| browser::find_html_element_by_id("new_game"))
-            .map(|element| engine::add_click_handler(element))
+            .map(engine::add_click_handler)
             .unwrap();
 
         WalkTheDogState {
             _state: GameOver {
                 new_game_event: receiver,
+                score: self.walk.score,
             },
             walk: self.walk,
         }
@@ -245,6 +442,8 @@ impl WalkTheDogState<Walking> {
 
 impl WalkTheDogState<GameOver> {
     fn update(mut self) -> GameOverEndState {
+        self.walk.effects.update();
+        update_particles(&mut self.walk.world);
         if self._state.new_game_pressed() {
             GameOverEndState::Complete(self.new_game())
         } else {
@@ -267,6 +466,53 @@ impl GameOver {
     fn new_game_pressed(&mut self) -> bool {
         matches!(self.new_game_event.try_next(), Ok(Some(())))
     }
+
+    fn draw<'a>(&'a self, queue: &mut RenderQueue<'a>) {
+        queue.submit(Layer::Hud, 0, |renderer| {
+            if let Err(err) = renderer.draw_text_box(
+                &format!("Final Score {}", self.score.points()),
+                &FINAL_SCORE_BOX,
+                &FINAL_SCORE_TEXT,
+            ) {
+                error!("Could not draw final score {:#?}", err);
+            }
+        });
+    }
+}
+
+fn record_high_score(score: &Score) -> (HighScores, Option<usize>) {
+    let mut storage = browser::storage();
+    let mut high_scores = HighScores::load(storage.as_ref()).unwrap_or_else(|err| {
+        error!("Could not load high scores {:#?}", err);
+        HighScores::default()
+    });
+    let rank = high_scores.insert(score);
+    if let Err(err) = high_scores.save(storage.as_mut()) {
+        error!("Could not save high scores {:#?}", err);
+    }
+
+    (high_scores, rank)
+}
+
+fn game_over_ui(high_scores: &HighScores, rank: Option<usize>) -> String {
+    let rows: Vec<String> = high_scores
+        .entries()
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            let class = if Some(index) == rank {
+                " class='current'"
+            } else {
+                ""
+            };
+            format!("<li{}>{} pts {}m</li>", class, entry.points, entry.meters)
+        })
+        .collect();
+
+    format!(
+        "<div><button id='new_game'>New Game</button><ol id='high_scores'>{}</ol></div>",
+        rows.join("")
+    )
 }
 
 impl From<WalkTheDogState<Ready>> for WalkTheDogStateMachine {
@@ -315,52 +561,206 @@ impl From<GameOverEndState> for WalkTheDogStateMachine {
 }
 
 pub struct Walk {
-    obstacle_sheet: Rc<SpriteSheet>,
     boy: RedHatBoy,
-    backgrounds: [Image; 2],
+    dog: Dog,
+    camera: Camera,
+    effects: Effects,
+    font: BitmapFont,
+    background: ParallaxBackground,
+    world: World<Tag>,
+    systems: Systems<Tag>,
     obstacles: Vec<Box<dyn Obstacle>>,
-    stone: HtmlImageElement,
+    segment_assets: SegmentAssets,
+    audio: Audio,
+    pickup_sound: Sound,
     timeline: i16,
+    score: Score,
+    difficulty: Difficulty,
+    segments: SegmentLibrary,
 }
 
 impl Walk {
-    fn draw(&self, renderer: &Renderer) {
-        self.backgrounds.iter().for_each(|background| {
-            background
-                .draw(renderer)
-                .expect("Failed to draw background.");
+    fn draw<'a>(&'a self, queue: &mut RenderQueue<'a>) {
+        queue.with_offset(self.effects.shake(), |queue| {
+            queue.submit(Layer::Background, 0, |renderer| {
+                self.background.draw(renderer)
+            });
+            queue.with_camera(&self.camera, |queue| {
+                queue.submit(Layer::World, GROUND_Z, |renderer| {
+                    self.world.draw(renderer);
+                    self.world.draw_colliders(renderer);
+                });
+                self.obstacles.iter().for_each(|obstacle| {
+                    queue.submit(Layer::World, OBSTACLE_Z, |renderer| {
+                        obstacle.draw(renderer);
+                        obstacle.draw_bounding_box(renderer);
+                    });
+                });
+                queue.submit(Layer::World, DOG_Z, |renderer| self.dog.draw(renderer));
+                queue.submit(Layer::World, BOY_Z, |renderer| {
+                    self.boy.draw(renderer);
+                    self.boy.draw_bounding_box(renderer);
+                });
+                queue.submit(Layer::World, PARTICLE_Z, |renderer| {
+                    self.world.draw_particles(renderer)
+                });
+            });
         });
-        self.boy.draw(renderer);
-        self.boy.draw_bounding_box(renderer);
-        self.obstacles.iter().for_each(|obstacle| {
-            obstacle.draw(renderer);
-            obstacle.draw_bounding_box(renderer);
+        queue.submit(Layer::Overlay, 0, |renderer| {
+            self.effects.draw(renderer, &SCREEN)
         });
+        queue.submit(Layer::Hud, 0, |renderer| self.draw_hud(renderer));
+    }
+
+    fn draw_hud(&self, renderer: &Renderer) {
+        let text = self.hud_text();
+        let health_bar = self.health_bar();
+        let panel = Rect::new_from_x_y(
+            HUD_POSITION.x - HUD_PANEL_PADDING,
+            HUD_POSITION.y - HUD_PANEL_PADDING,
+            self.font
+                .width(&text)
+                .max(health_bar.right() - HUD_POSITION.x)
+                + 2 * HUD_PANEL_PADDING,
+            health_bar.bottom() - HUD_POSITION.y + 2 * HUD_PANEL_PADDING,
+        );
+        if let Err(err) = renderer
+            .draw_rounded_rect(&panel, HUD_PANEL_RADIUS, &HUD_PANEL)
+            .and_then(|_| self.font.draw(renderer, &text, HUD_POSITION))
+        {
+            error!("Could not draw HUD {:#?}", err);
+        }
+        let health = f32::from(self.boy.lives()) / f32::from(self.boy.starting_lives.max(1));
+        renderer.draw_rect(&health_bar, &HEALTH_BAR_BACK);
+        renderer.draw_rect(&shapes::bar_fill(&health_bar, health), &HEALTH_BAR_FILL);
+        renderer.draw_rect(&health_bar, &HEALTH_BAR_EDGE);
+    }
+
+    /// The bitmap font only has capitals.
+    fn hud_text(&self) -> String {
+        format!(
+            "DISTANCE {}M\nOBSTACLES {}\nSCORE {}\nLIVES",
+            self.score.meters(),
+            self.score.obstacles_cleared(),
+            self.score.points(),
+        )
+    }
+
+    fn health_bar(&self) -> Rect {
+        Rect::new_from_x_y(
+            HUD_POSITION.x + self.font.width("LIVES "),
+            HUD_POSITION.y + 3 * self.font.line_height() + 2,
+            HEALTH_BAR_WIDTH,
+            HEALTH_BAR_HEIGHT,
+        )
+    }
+
+    fn follow_boy(&mut self) {
+        let previous = self.camera.position().x;
+        self.camera.follow(self.boy.position());
+        self.background.scroll(previous - self.camera.position().x);
+        wrap_ground(&mut self.world, self.camera.view().x());
+    }
+
+    fn recenter(&mut self) {
+        let distance = -self.camera.position().x;
+        self.camera.move_horizontally(distance);
+        self.boy.move_horizontally(distance);
+        self.dog.move_horizontally(distance);
+        self.obstacles
+            .iter_mut()
+            .for_each(|obstacle| obstacle.move_horizontally(distance));
+        self.world
+            .positions
+            .iter_mut()
+            .for_each(|(_, position)| position.x += distance);
+        self.timeline += distance;
     }
 
     fn velocity(&self) -> i16 {
-        -self.boy.walk_speed()
+        if self.boy.walk_speed() == 0 {
+            0
+        } else {
+            self.difficulty.stage_at(self.score.meters()).running_speed
+        }
     }
 
     fn generate_next_segment(&mut self) {
         let mut rng = thread_rng();
-        let next_segment = rng.gen_range(0..2);
+        let stage = self.difficulty.stage_at(self.score.meters());
+        let offset_x = self.timeline + stage.segment_gap;
 
-        let mut next_obstacles = match next_segment {
-            0 => stone_and_platform(
-                self.stone.clone(),
-                self.obstacle_sheet.clone(),
-                self.timeline + OBSTACLE_BUFFER,
-            ),
-            1 => platform_and_stone(
-                self.stone.clone(),
-                self.obstacle_sheet.clone(),
-                self.timeline + OBSTACLE_BUFFER,
-            ),
-            _ => vec![],
+        if let Some(name) = stage.segments.choose(&mut rng).cloned() {
+            self.place_segment(&name, offset_x);
+        }
+    }
+
+    fn place_starting_segment(&mut self) {
+        self.obstacles.clear();
+        self.timeline = 0;
+        if let Some(name) = self.difficulty.stage_at(0).segments.first().cloned() {
+            self.place_segment(&name, STARTING_SEGMENT_OFFSET);
+        }
+    }
+
+    fn place_segment(&mut self, name: &str, offset_x: i16) {
+        let Some(segment) = self.segments.segment(name) else {
+            error!("Segment {} not found", name);
+            return;
         };
-        self.timeline = rightmost(&next_obstacles);
-        self.obstacles.append(&mut next_obstacles);
+        let mut obstacles = segment.build(&self.segment_assets, offset_x);
+        let pickups: Vec<Entity> = segment
+            .pickups(offset_x)
+            .into_iter()
+            .filter_map(|(pickup, position)| {
+                spawn_pickup(
+                    &mut self.world,
+                    self.segment_assets.pickups.clone(),
+                    pickup,
+                    position,
+                )
+            })
+            .collect();
+        self.timeline = pickups
+            .into_iter()
+            .filter_map(|pickup| self.world.bounding_box(pickup))
+            .map(|bounding_box| bounding_box.right())
+            .fold(rightmost(&obstacles), i16::max);
+        self.obstacles.append(&mut obstacles);
+    }
+
+    fn collect_pickups(&mut self) {
+        self.world
+            .colliding_with(&self.boy.bounding_box())
+            .into_iter()
+            .for_each(|entity| {
+                if let Some(&Tag::Pickup { pickup, .. }) = self.world.tags.get(entity) {
+                    self.collect(entity, pickup);
+                }
+            });
+    }
+
+    fn collect(&mut self, entity: Entity, pickup: Pickup) {
+        let x = self
+            .world
+            .positions
+            .get(entity)
+            .map_or(0, |position| position.x);
+        self.world.despawn(entity);
+        self.score.collect(pickup.points());
+        if pickup == Pickup::Heart {
+            self.boy.gain_life();
+        }
+        if let Err(err) = self
+            .audio
+            .play_sound_at(&self.pickup_sound, self.screen_x(x), WIDTH)
+        {
+            log!("Error playing pickup sound {:#?}", err);
+        }
+    }
+
+    fn screen_x(&self, x: i16) -> i16 {
+        self.camera.on_screen(Point { x, y: 0 }).x
     }
 
     fn knocked_out(&self) -> bool {
@@ -368,33 +768,316 @@ impl Walk {
     }
 
     fn reset(walk: Self) -> Self {
-        let starting_obstacles =
-            stone_and_platform(walk.stone.clone(), walk.obstacle_sheet.clone(), 0);
-        let timeline = rightmost(&starting_obstacles);
-
-        Walk {
+        let mut world = walk.world;
+        world.particles.clear();
+        world
+            .tagged(|tag| matches!(tag, Tag::Ground | Tag::Pickup { .. }))
+            .into_iter()
+            .for_each(|entity| world.despawn(entity));
+        spawn_ground(&mut world, walk.segment_assets.tiles.clone());
+        let mut walk = Walk {
             boy: RedHatBoy::reset(walk.boy),
-            backgrounds: walk.backgrounds,
-            obstacles: starting_obstacles,
-            obstacle_sheet: walk.obstacle_sheet,
-            stone: walk.stone,
-            timeline,
+            dog: Dog::reset(walk.dog),
+            camera: new_camera(),
+            effects: Effects::new(thread_rng().gen()),
+            font: walk.font,
+            background: walk.background,
+            world,
+            systems: walk.systems,
+            obstacles: vec![],
+            segment_assets: walk.segment_assets,
+            audio: walk.audio,
+            pickup_sound: walk.pickup_sound,
+            timeline: 0,
+            score: Score::new(),
+            difficulty: walk.difficulty,
+            segments: walk.segments,
+        };
+        walk.place_starting_segment();
+        walk
+    }
+}
+
+/// Dust while the boy runs, a puff when he lands and sparks when he's hurt.
+fn spawn_emitters(world: &mut World<Tag>) {
+    let dust = world.particles.add_style(ParticleStyle {
+        lifetime: 24,
+        velocity_x: -1.5..=0.0,
+        velocity_y: -1.2..=-0.3,
+        gravity: 0.05,
+        size: 6,
+        start_color: Color::rgba(230, 220, 200, 0.7),
+        end_color: Color::rgba(230, 220, 200, 0.0),
+        frames: puff_frames(),
+    });
+    let landing = world.particles.add_style(ParticleStyle {
+        lifetime: 20,
+        velocity_x: -2.5..=2.5,
+        velocity_y: -1.5..=-0.2,
+        gravity: 0.08,
+        size: 8,
+        start_color: Color::rgba(240, 235, 220, 0.9),
+        end_color: Color::rgba(240, 235, 220, 0.0),
+        frames: puff_frames(),
+    });
+    let sparks = world.particles.add_style(ParticleStyle {
+        lifetime: 36,
+        velocity_x: -4.0..=4.0,
+        velocity_y: -7.0..=-2.0,
+        gravity: 0.35,
+        size: 5,
+        start_color: Color::rgba(255, 230, 90, 1.0),
+        end_color: Color::rgba(230, 40, 20, 0.0),
+        frames: vec![],
+    });
+    [
+        (
+            Tag::Dust,
+            Emitter::new(dust, Emission::Continuous(DUST_PER_FRAME)),
+        ),
+        (
+            Tag::Landing,
+            Emitter::new(landing, Emission::Burst(LANDING_PUFFS)),
+        ),
+        (
+            Tag::Sparks,
+            Emitter::new(sparks, Emission::Burst(IMPACT_SPARKS)),
+        ),
+    ]
+    .into_iter()
+    .for_each(|(tag, emitter)| {
+        let entity = world.spawn(tag);
+        world.emitters.insert(entity, emitter);
+    });
+}
+
+fn emit_particles(world: &mut World<Tag>, boy: &RedHatBoy, landed: bool, impact: Option<Impact>) {
+    let body = boy.bounding_box();
+    if boy.running() {
+        emit_from(
+            world,
+            Tag::Dust,
+            Point {
+                x: body.x(),
+                y: body.bottom(),
+            },
+        );
+    }
+    if landed {
+        emit_from(
+            world,
+            Tag::Landing,
+            Point {
+                x: body.x() + body.w / 2,
+                y: body.bottom(),
+            },
+        );
+    }
+    if impact.is_some() {
+        emit_from(
+            world,
+            Tag::Sparks,
+            Point {
+                x: body.x() + body.w / 2,
+                y: body.y() + body.h / 2,
+            },
+        );
+    }
+}
+
+fn emit_from(world: &mut World<Tag>, emitter: Tag, position: Point) {
+    world
+        .tagged(|tag| *tag == emitter)
+        .into_iter()
+        .for_each(|entity| {
+            world.positions.insert(entity, position);
+            world.emit(entity);
+        });
+}
+
+fn puff_frames() -> Vec<String> {
+    (1..=4)
+        .map(|frame| format!("Puff ({}).png", frame))
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Impact {
+    Hit,
+    Fall,
+}
+
+impl Impact {
+    fn apply(self, effects: &mut Effects) {
+        match self {
+            Impact::Hit => {
+                effects.add_trauma(HIT_TRAUMA);
+                effects.hit_stop(HIT_STOP_FRAMES);
+                effects.flash(HIT_FLASH);
+            }
+            Impact::Fall => effects.add_trauma(FALL_TRAUMA),
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum Tag {
+    Ground,
+    Pickup { pickup: Pickup, spin: u16 },
+    Dust,
+    Landing,
+    Sparks,
+}
+
+fn spawn_ground(world: &mut World<Tag>, tiles: Rc<SpriteSheet>) {
+    let width = tiles.cell(GROUND_SPRITE).map_or(0, |cell| cell.frame.w);
+    if width <= 0 {
+        error!("Ground sprite {} not found", GROUND_SPRITE);
+        return;
+    }
+    (0..WIDTH / width + 2).for_each(|column| {
+        let ground = world.spawn(Tag::Ground);
+        world.positions.insert(
+            ground,
+            Point {
+                x: column * width,
+                y: HEIGHT,
+            },
+        );
+        world.sprites.insert(
+            ground,
+            Sprite::Cell {
+                sheet: tiles.clone(),
+                name: GROUND_SPRITE.into(),
+            },
+        );
+    });
+}
+
+fn new_camera() -> Camera {
+    Camera::new(WIDTH, HEIGHT + GROUND_DEPTH)
+        .with_look_ahead(CAMERA_LOOK_AHEAD)
+        .with_dead_zone(CAMERA_DEAD_ZONE_TOP, CAMERA_DEAD_ZONE_BOTTOM)
+}
+
+fn spawn_pickup(
+    world: &mut World<Tag>,
+    sheet: Rc<SpriteSheet>,
+    pickup: Pickup,
+    position: Point,
+) -> Option<Entity> {
+    let Some(cell) = sheet.cell(pickup.sprite_name()) else {
+        error!("Pickup sprite {} not found", pickup.sprite_name());
+        return None;
+    };
+    let collider = Rect::new_from_x_y(0, 0, cell.frame.w, cell.frame.h);
+    let entity = world.spawn(Tag::Pickup { pickup, spin: 0 });
+    world.positions.insert(entity, position);
+    world.colliders.insert(entity, collider);
+    world.sprites.insert(
+        entity,
+        Sprite::Cell {
+            sheet,
+            name: pickup.sprite_name().into(),
+        },
+    );
+    Some(entity)
+}
+
+fn walk_systems() -> Systems<Tag> {
+    Systems::new()
+        .with(movement)
+        .with(spin_pickups)
+        .with(update_particles)
+}
+
+/// Pickups are drawn mirrored while they face away.
+fn spin_pickups(world: &mut World<Tag>) {
+    let World { tags, params, .. } = world;
+    tags.iter_mut().for_each(|(entity, tag)| {
+        if let Tag::Pickup { spin, .. } = tag {
+            *spin = (*spin + 1) % PICKUP_SPIN_FRAMES;
+            let angle = std::f32::consts::TAU * f32::from(*spin) / f32::from(PICKUP_SPIN_FRAMES);
+            params.insert(
+                entity,
+                DrawParams {
+                    scale_x: angle.cos(),
+                    ..DrawParams::default()
+                },
+            );
+        }
+    });
+}
+
+fn despawn_passed_pickups(world: &mut World<Tag>, view_left: i16) {
+    world
+        .tagged(|tag| matches!(tag, Tag::Pickup { .. }))
+        .into_iter()
+        .filter(|&pickup| {
+            world
+                .bounding_box(pickup)
+                .map_or(true, |bounding_box| bounding_box.right() <= view_left)
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .for_each(|pickup| world.despawn(pickup));
+}
+
+fn wrap_ground(world: &mut World<Tag>, view_left: i16) {
+    wrap_row(world, Tag::Ground, view_left);
+}
+
+/// Moves an entity the camera has left behind to the end of its row.
+fn wrap_row(world: &mut World<Tag>, row: Tag, view_left: i16) {
+    let entities = world.tagged(|tag| *tag == row);
+    let right_of = |world: &World<Tag>, entity| {
+        let x = world.positions.get(entity).map_or(0, |position| position.x);
+        x + world.sprites.get(entity).map_or(0, Sprite::width)
+    };
+    for &entity in &entities {
+        if right_of(world, entity) < view_left {
+            let rightmost = entities
+                .iter()
+                .map(|&other| right_of(world, other))
+                .max()
+                .unwrap_or(0);
+            if let Some(position) = world.positions.get_mut(entity) {
+                position.x = rightmost;
+            }
         }
     }
 }
 
-struct RedHatBoy {
+pub struct RedHatBoy {
     state_machine: RedHatBoyStateMachine,
     sprite_sheet: Sheet,
     image: HtmlImageElement,
+    audio: Audio,
+    jump_sound: Sound,
+    starting_lives: u8,
+    movement: Movement,
 }
 
 impl RedHatBoy {
-    fn new(sheet: Sheet, image: HtmlImageElement, audio: Audio, jump_sound: Sound) -> Self {
+    fn new(
+        sheet: Sheet,
+        image: HtmlImageElement,
+        audio: Audio,
+        jump_sound: Sound,
+        starting_lives: u8,
+        movement: Movement,
+    ) -> Self {
         RedHatBoy {
-            state_machine: RedHatBoyStateMachine::Idle(RedHatBoyState::new(audio, jump_sound)),
+            state_machine: RedHatBoyStateMachine::Idle(RedHatBoyState::new(
+                starting_lives,
+                movement,
+            )),
             sprite_sheet: sheet,
             image,
+            audio,
+            jump_sound,
+            starting_lives,
+            movement,
         }
     }
 
@@ -402,73 +1085,64 @@ impl RedHatBoy {
         RedHatBoy::new(
             boy.sprite_sheet,
             boy.image,
-            boy.state_machine.context().audio.clone(),
-            boy.state_machine.context().jump_sound.clone(),
+            boy.audio,
+            boy.jump_sound,
+            boy.starting_lives,
+            boy.movement,
         )
     }
 
-    fn frame_name(&self) -> String {
-        format!(
-            "{} ({}).png",
-            self.state_machine.frame_name(),
-            (self.state_machine.context().frame / 3) + 1,
-        )
+    fn lives(&self) -> u8 {
+        self.state_machine.context().lives
     }
 
     fn current_sprite(&self) -> Option<&Cell> {
-        self.sprite_sheet.frames.get(&self.frame_name())
+        self.state_machine.current_sprite(&self.sprite_sheet)
     }
 
-    fn pos_y(&self) -> i16 {
-        self.state_machine.context().position.y
+    fn walk_speed(&self) -> i16 {
+        self.state_machine.context().velocity.x
     }
 
-    fn velocity_y(&self) -> i16 {
-        self.state_machine.context().velocity.y
+    fn position(&self) -> Point {
+        self.state_machine.context().position
     }
 
-    fn walk_speed(&self) -> i16 {
-        self.state_machine.context().velocity.x
+    fn move_horizontally(&mut self, x: i16) {
+        self.transition(Event::Move(x))
     }
 
     fn destination_box(&self) -> Rect {
-        let sprite = self.current_sprite().expect("Cell not found");
-
-        Rect::new_from_x_y(
-            (self.state_machine.context().position.x + sprite.sprite_source_size.x).into(),
-            (self.state_machine.context().position.y + sprite.sprite_source_size.y).into(),
-            sprite.frame.w.into(),
-            sprite.frame.h.into(),
-        )
+        self.state_machine.destination_box(&self.sprite_sheet)
     }
 
-    fn bounding_box(&self) -> Rect {
-        const X_OFFSET: f32 = 18.0;
-        const Y_OFFSET: f32 = 14.0;
-        const WIDTH_OFFSET: f32 = 28.0;
-        let mut bounding_box = self.destination_box();
-        bounding_box.set_x(bounding_box.x() + X_OFFSET as i16);
-        bounding_box.set_y(bounding_box.y() + Y_OFFSET as i16);
-        bounding_box.w -= WIDTH_OFFSET as i16;
-        bounding_box.h -= Y_OFFSET as i16;
-        bounding_box
+    fn update(&mut self) {
+        self.transition(Event::Update)
     }
 
-    fn update(&mut self) {
-        self.state_machine = self.state_machine.clone().update()
+    /// Plays the jump sound whenever the boy takes off.
+    fn transition(&mut self, event: Event) {
+        let was_taking_off = self.state_machine.taking_off();
+        self.state_machine = self.state_machine.clone().transition(event);
+        if self.state_machine.taking_off() && !was_taking_off {
+            self.play_jump_sound();
+        }
     }
 
     fn draw(&self, renderer: &Renderer) {
+        if !self.state_machine.visible() {
+            return;
+        }
         let sprite = self.current_sprite().expect("Cell not found");
 
         renderer
             .draw_image(
                 &self.image,
                 &Rect::new_from_x_y(
-                    sprite.frame.x.into(),
-                    sprite.frame.y.into(),
-                    sprite.frame.w.into(),
-                    sprite.frame.h.into(),
+                    sprite.frame.x,
+                    sprite.frame.y,
+                    sprite.frame.w,
+                    sprite.frame.h,
                 ),
                 &self.destination_box(),
             )
@@ -476,7 +1150,7 @@ impl RedHatBoy {
     }
 
     fn draw_bounding_box(&self, renderer: &Renderer) {
-        renderer.draw_rect(&self.bounding_box());
+        renderer.draw_rect(&self.bounding_box(), &DEBUG_OUTLINE);
     }
 
     fn run_right(&mut self) {
@@ -488,109 +1162,398 @@ impl RedHatBoy {
     }
 
     fn jump(&mut self) {
-        self.state_machine = self.state_machine.clone().transition(Event::Jump)
+        self.transition(Event::Jump)
+    }
+
+    fn play_jump_sound(&self) {
+        if let Err(err) = self
+            .audio
+            .play_sound_at(&self.jump_sound, BOY_SCREEN_X, WIDTH)
+        {
+            log!("Error playing jump sound {:#?}", err);
+        }
     }
 
     fn land_on(&mut self, position: i16) {
-        self.state_machine = self.state_machine.clone().transition(Event::Land(position))
+        self.transition(Event::Land(position))
     }
 
     fn knock_out(&mut self) {
-        self.state_machine = self.state_machine.clone().transition(Event::KnockOut)
+        self.transition(Event::KnockOut)
     }
 
-    fn knocked_out(&self) -> bool {
-        self.state_machine.knocked_out()
+    fn gain_life(&mut self) {
+        self.transition(Event::GainLife)
     }
-}
 
-pub trait Obstacle {
-    fn right(&self) -> i16;
-    fn check_intersection(&self, boy: &mut RedHatBoy);
-    fn draw(&self, renderer: &Renderer);
-    fn move_horizontally(&mut self, x: i16);
-    fn draw_bounding_box(&self, renderer: &Renderer);
-}
+    fn over_pit(&mut self) {
+        self.transition(Event::OverPit)
+    }
 
-pub(crate) struct Platform {
-    sheet: Rc<SpriteSheet>,
-    bounding_boxes: Vec<Rect>,
-    sprites: Vec<Cell>,
-    position: Point,
-}
+    fn bounce(&mut self) {
+        self.transition(Event::Bounce)
+    }
 
-impl Platform {
-    pub(crate) fn new(
-        sheet: Rc<SpriteSheet>,
-        position: Point,
-        sprite_names: &[&str],
-        bounding_boxes: &[Rect],
-    ) -> Self {
-        let sprites = sprite_names
-            .iter()
-            .filter_map(|sprite_name| sheet.cell(sprite_name).cloned())
-            .collect();
-        let bounding_boxes = bounding_boxes
-            .iter()
-            .map(|bounding_boxes| {
-                Rect::new_from_x_y(
-                    bounding_boxes.x() + position.x,
-                    bounding_boxes.y() + position.y,
-                    bounding_boxes.w,
-                    bounding_boxes.h,
-                )
-            })
-            .collect();
-        Platform {
-            sheet,
-            position,
-            sprites,
-            bounding_boxes,
+    fn collide(&mut self, collision: Collision) {
+        match collision {
+            Collision::Land(position) => self.land_on(position),
+            Collision::Stomp => self.bounce(),
+            Collision::KnockOut => self.knock_out(),
+            Collision::OverPit => self.over_pit(),
         }
     }
 
-    fn bounding_boxes(&self) -> &Vec<Rect> {
-        &self.bounding_boxes
+    fn jumping(&self) -> bool {
+        matches!(self.state_machine, RedHatBoyStateMachine::Jumping(_))
+    }
+
+    fn running(&self) -> bool {
+        matches!(self.state_machine, RedHatBoyStateMachine::Running(_))
+    }
+
+    fn airborne(&self) -> bool {
+        matches!(
+            self.state_machine,
+            RedHatBoyStateMachine::Jumping(_) | RedHatBoyStateMachine::Airborne(_)
+        )
+    }
+
+    fn grounded(&self) -> bool {
+        matches!(
+            self.state_machine,
+            RedHatBoyStateMachine::Running(_) | RedHatBoyStateMachine::Sliding(_)
+        )
+    }
+
+    fn knocked_out(&self) -> bool {
+        self.state_machine.knocked_out()
     }
 }
 
-impl Obstacle for Platform {
-    fn right(&self) -> i16 {
-        self.bounding_boxes()
-            .last()
-            .unwrap_or(&Rect::default())
-            .right()
+impl Actor for RedHatBoy {
+    fn bounding_box(&self) -> Rect {
+        self.state_machine.bounding_box(&self.sprite_sheet)
     }
 
-    fn check_intersection(&self, boy: &mut RedHatBoy) {
-        if let Some(box_to_land_on) = self
-            .bounding_boxes()
-            .iter()
-            .find(|&bounding_box| boy.bounding_box().interests(bounding_box))
-        {
-            if boy.velocity_y() > 0 && boy.pos_y() < self.position.y {
-                boy.land_on(box_to_land_on.y());
-            } else {
-                boy.knock_out();
-            }
-        }
+    fn pos_y(&self) -> i16 {
+        self.state_machine.context().position.y
     }
 
-    fn draw(&self, renderer: &Renderer) {
-        let mut x = 0;
-        self.sprites.iter().for_each(|sprite| {
-            self.sheet
-                .draw(
-                    renderer,
-                    &Rect::new_from_x_y(
+    fn velocity_y(&self) -> i16 {
+        self.state_machine.context().velocity.y
+    }
+}
+
+/// Anything that can run into an obstacle.
+pub trait Actor {
+    fn bounding_box(&self) -> Rect;
+    fn pos_y(&self) -> i16;
+    fn velocity_y(&self) -> i16;
+}
+
+/// What an obstacle decided should happen to the actor that touched it.
+/// Obstacles only report collisions; the game applies them to the actor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Collision {
+    Land(i16),
+    /// The actor came down on top of an enemy.
+    Stomp,
+    KnockOut,
+    /// There's no ground under the actor, so it falls if it comes down here.
+    OverPit,
+}
+
+/// Actors only drop into a pit once they're entirely over it.
+fn pit_collision(actor: &dyn Actor, gap: &Rect) -> Option<Collision> {
+    let actor_box = actor.bounding_box();
+    (actor_box.x() >= gap.x() && actor_box.right() <= gap.right()).then_some(Collision::OverPit)
+}
+
+fn barrier_collision(actor: &dyn Actor, bounding_box: &Rect) -> Option<Collision> {
+    actor
+        .bounding_box()
+        .interests(bounding_box)
+        .then_some(Collision::KnockOut)
+}
+
+/// Actors coming down from above `top` land on the box they touched, and
+/// anything else runs into the side of the platform.
+fn platform_collision(actor: &dyn Actor, top: i16, bounding_boxes: &[Rect]) -> Option<Collision> {
+    let actor_box = actor.bounding_box();
+    bounding_boxes
+        .iter()
+        .find(|&bounding_box| actor_box.interests(bounding_box))
+        .map(|box_to_land_on| {
+            if actor.velocity_y() > 0 && actor.pos_y() < top {
+                Collision::Land(box_to_land_on.y())
+            } else {
+                Collision::KnockOut
+            }
+        })
+}
+
+/// Only actors whose feet were above the platform a frame ago land on it.
+fn one_way_collision(actor: &dyn Actor, bounding_boxes: &[Rect]) -> Option<Collision> {
+    let actor_box = actor.bounding_box();
+    let previous_bottom = actor_box.bottom() - actor.velocity_y();
+    bounding_boxes
+        .iter()
+        .find(|&bounding_box| actor_box.interests(bounding_box))
+        .filter(|bounding_box| {
+            actor.velocity_y() > 0 && previous_bottom <= bounding_box.y() + ONE_WAY_TOLERANCE
+        })
+        .map(|box_to_land_on| Collision::Land(box_to_land_on.y()))
+}
+
+/// Actors whose feet were above an enemy a frame ago stomp on it.
+fn enemy_collision(
+    actor: &dyn Actor,
+    body: Option<&Rect>,
+    projectiles: &[Rect],
+) -> Option<Collision> {
+    let actor_box = actor.bounding_box();
+    let previous_bottom = actor_box.bottom() - actor.velocity_y();
+    body.filter(|body| actor_box.interests(body))
+        .map(|body| {
+            if actor.velocity_y() > 0 && previous_bottom <= body.y() {
+                Collision::Stomp
+            } else {
+                Collision::KnockOut
+            }
+        })
+        .or_else(|| {
+            projectiles
+                .iter()
+                .any(|projectile| actor_box.interests(projectile))
+                .then_some(Collision::KnockOut)
+        })
+}
+
+pub trait Obstacle {
+    fn left(&self) -> i16;
+    fn right(&self) -> i16;
+    fn check_intersection(&self, actor: &dyn Actor) -> Option<Collision>;
+    fn draw(&self, renderer: &Renderer);
+    fn move_horizontally(&mut self, x: i16);
+    fn draw_bounding_box(&self, renderer: &Renderer);
+
+    /// Called once a frame with the part of the world that's on screen.
+    fn update(&mut self, _view: &Rect) {}
+
+    /// Called when an actor lands on top of this obstacle.
+    fn landed_on(&mut self) {}
+
+    /// Whether getting past this counts as clearing an obstacle.
+    fn is_hazard(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlatformBehavior {
+    #[default]
+    Static,
+    /// Bobs `amplitude` pixels above and below where it was placed, once
+    /// every `period` frames.
+    Oscillating { amplitude: i16, period: u16 },
+    /// Falls away `delay` frames after something lands on it.
+    Crumbling { delay: u16 },
+    /// Can be jumped up through and only landed on from above.
+    OneWay,
+}
+
+/// How far a platform moves by itself each frame.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct PlatformMotion {
+    behavior: PlatformBehavior,
+    frame: u16,
+    offset_y: i16,
+    crumble_in: Option<u16>,
+    fall_velocity: i16,
+}
+
+impl PlatformMotion {
+    fn new(behavior: PlatformBehavior) -> Self {
+        PlatformMotion {
+            behavior,
+            frame: 0,
+            offset_y: 0,
+            crumble_in: None,
+            fall_velocity: 0,
+        }
+    }
+
+    /// Advances one frame and returns how far down the platform moved.
+    fn update(&mut self) -> i16 {
+        match self.behavior {
+            PlatformBehavior::Oscillating { amplitude, period } => {
+                let period = period.max(1);
+                self.frame = (self.frame + 1) % period;
+                let angle = std::f32::consts::TAU * self.frame as f32 / period as f32;
+                let offset_y = (amplitude as f32 * angle.sin()).round() as i16;
+                let distance = offset_y - self.offset_y;
+                self.offset_y = offset_y;
+                distance
+            }
+            PlatformBehavior::Crumbling { .. } => match self.crumble_in {
+                Some(0) => {
+                    self.fall_velocity =
+                        (self.fall_velocity + CRUMBLE_GRAVITY).min(CRUMBLE_TERMINAL_VELOCITY);
+                    self.fall_velocity
+                }
+                Some(frames) => {
+                    self.crumble_in = Some(frames - 1);
+                    0
+                }
+                None => 0,
+            },
+            PlatformBehavior::Static | PlatformBehavior::OneWay => 0,
+        }
+    }
+
+    fn land(&mut self) {
+        if let PlatformBehavior::Crumbling { delay } = self.behavior {
+            self.crumble_in.get_or_insert(delay);
+        }
+    }
+
+    /// Crumbling platforms can't be stood on once they start to fall.
+    fn solid(&self) -> bool {
+        self.fall_velocity == 0
+    }
+
+    fn one_way(&self) -> bool {
+        self.behavior == PlatformBehavior::OneWay
+    }
+}
+
+/// A platform without its sprites, so the simulation can move it too.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct PlatformBody {
+    position: Point,
+    bounding_boxes: Vec<Rect>,
+    motion: PlatformMotion,
+}
+
+impl PlatformBody {
+    /// `bounding_boxes` are relative to `position`.
+    fn new(position: Point, bounding_boxes: &[Rect], behavior: PlatformBehavior) -> Self {
+        let bounding_boxes = bounding_boxes
+            .iter()
+            .map(|bounding_boxes| {
+                Rect::new_from_x_y(
+                    bounding_boxes.x() + position.x,
+                    bounding_boxes.y() + position.y,
+                    bounding_boxes.w,
+                    bounding_boxes.h,
+                )
+            })
+            .collect();
+        PlatformBody {
+            position,
+            bounding_boxes,
+            motion: PlatformMotion::new(behavior),
+        }
+    }
+
+    fn left(&self) -> i16 {
+        self.bounding_boxes.first().unwrap_or(&Rect::default()).x()
+    }
+
+    fn right(&self) -> i16 {
+        self.bounding_boxes
+            .last()
+            .unwrap_or(&Rect::default())
+            .right()
+    }
+
+    fn check_intersection(&self, actor: &dyn Actor) -> Option<Collision> {
+        if !self.motion.solid() {
+            None
+        } else if self.motion.one_way() {
+            one_way_collision(actor, &self.bounding_boxes)
+        } else {
+            platform_collision(actor, self.position.y, &self.bounding_boxes)
+        }
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.position.x += x;
+        self.bounding_boxes
+            .iter_mut()
+            .for_each(|bounding_box| bounding_box.set_x(bounding_box.position.x + x))
+    }
+
+    fn update(&mut self) {
+        let distance = self.motion.update();
+        if distance != 0 {
+            self.position.y += distance;
+            self.bounding_boxes
+                .iter_mut()
+                .for_each(|bounding_box| bounding_box.set_y(bounding_box.y() + distance));
+        }
+    }
+
+    fn landed_on(&mut self) {
+        self.motion.land();
+    }
+}
+
+pub(crate) struct Platform {
+    sheet: Rc<SpriteSheet>,
+    sprites: Vec<Cell>,
+    body: PlatformBody,
+}
+
+impl Platform {
+    pub(crate) fn new(
+        sheet: Rc<SpriteSheet>,
+        position: Point,
+        sprite_names: &[&str],
+        bounding_boxes: &[Rect],
+        behavior: PlatformBehavior,
+    ) -> Self {
+        let sprites = sprite_names
+            .iter()
+            .filter_map(|sprite_name| sheet.cell(sprite_name).cloned())
+            .collect();
+        Platform {
+            sheet,
+            sprites,
+            body: PlatformBody::new(position, bounding_boxes, behavior),
+        }
+    }
+}
+
+impl Obstacle for Platform {
+    fn left(&self) -> i16 {
+        self.body.left()
+    }
+
+    fn right(&self) -> i16 {
+        self.body.right()
+    }
+
+    fn check_intersection(&self, actor: &dyn Actor) -> Option<Collision> {
+        self.body.check_intersection(actor)
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        let mut x = 0;
+        self.sprites.iter().for_each(|sprite| {
+            self.sheet
+                .draw(
+                    renderer,
+                    &Rect::new_from_x_y(
                         sprite.frame.x,
                         sprite.frame.y,
                         sprite.frame.w,
                         sprite.frame.h,
                     ),
                     &Rect::new_from_x_y(
-                        self.position.x + x,
-                        self.position.y,
+                        self.body.position.x + x,
+                        self.body.position.y,
                         sprite.frame.w,
                         sprite.frame.h,
                     ),
@@ -601,16 +1564,22 @@ impl Obstacle for Platform {
     }
 
     fn move_horizontally(&mut self, x: i16) {
-        self.position.x += x;
-        self.bounding_boxes
-            .iter_mut()
-            .for_each(|bounding_box| bounding_box.set_x(bounding_box.position.x + x))
+        self.body.move_horizontally(x);
     }
 
     fn draw_bounding_box(&self, renderer: &Renderer) {
-        self.bounding_boxes()
+        self.body
+            .bounding_boxes
             .iter()
-            .for_each(|bounding_box| renderer.draw_rect(bounding_box));
+            .for_each(|bounding_box| renderer.draw_rect(bounding_box, &DEBUG_OUTLINE));
+    }
+
+    fn update(&mut self, _view: &Rect) {
+        self.body.update();
+    }
+
+    fn landed_on(&mut self) {
+        self.body.landed_on();
     }
 }
 
@@ -625,14 +1594,16 @@ impl Barrier {
 }
 
 impl Obstacle for Barrier {
+    fn left(&self) -> i16 {
+        self.image.bounding_box().x()
+    }
+
     fn right(&self) -> i16 {
         self.image.right()
     }
 
-    fn check_intersection(&self, boy: &mut RedHatBoy) {
-        if boy.bounding_box().interests(self.image.bounding_box()) {
-            boy.knock_out();
-        }
+    fn check_intersection(&self, actor: &dyn Actor) -> Option<Collision> {
+        barrier_collision(actor, self.image.bounding_box())
     }
 
     fn draw(&self, renderer: &Renderer) {
@@ -648,12 +1619,366 @@ impl Obstacle for Barrier {
     }
 }
 
+/// A gap in the ground, filled with water drawn over the ground strip.
+pub(crate) struct Pit {
+    sheet: Rc<SpriteSheet>,
+    water: Cell,
+    gap: Rect,
+}
+
+impl Pit {
+    pub(crate) fn new(sheet: Rc<SpriteSheet>, water: Cell, x: i16, width: i16) -> Self {
+        Pit {
+            sheet,
+            water,
+            gap: Rect::new_from_x_y(x, HEIGHT, width, GROUND_DEPTH),
+        }
+    }
+}
+
+impl Obstacle for Pit {
+    fn left(&self) -> i16 {
+        self.gap.x()
+    }
+
+    fn right(&self) -> i16 {
+        self.gap.right()
+    }
+
+    fn check_intersection(&self, actor: &dyn Actor) -> Option<Collision> {
+        pit_collision(actor, &self.gap)
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        let frame = &self.water.frame;
+        (self.gap.x()..self.gap.right())
+            .step_by(frame.w.max(1) as usize)
+            .for_each(|x| {
+                let w = frame.w.min(self.gap.right() - x);
+                let h = frame.h.min(GROUND_DEPTH);
+                self.sheet
+                    .draw(
+                        renderer,
+                        &Rect::new_from_x_y(frame.x, frame.y, w, h),
+                        &Rect::new_from_x_y(x, self.gap.y(), w, h),
+                    )
+                    .expect("failed to draw pit.");
+            });
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.gap.set_x(self.gap.x() + x);
+    }
+
+    fn draw_bounding_box(&self, renderer: &Renderer) {
+        renderer.draw_rect(&self.gap, &DEBUG_OUTLINE);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pickup {
+    Coin,
+    Gem,
+    Heart,
+}
+
+impl Pickup {
+    pub fn sprite_name(&self) -> &'static str {
+        match self {
+            Pickup::Coin => "Coin.png",
+            Pickup::Gem => "Gem.png",
+            Pickup::Heart => "Heart.png",
+        }
+    }
+
+    pub fn points(&self) -> u32 {
+        match self {
+            Pickup::Coin => 5,
+            Pickup::Gem => 25,
+            Pickup::Heart => 50,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EnemyBehavior {
+    /// Paces `distance` pixels to the left of where it was placed and back.
+    Walker { distance: i16, speed: i16 },
+    /// Flies left at `speed`, bobbing `amplitude` pixels up and down once
+    /// every `period` frames.
+    Flier {
+        speed: i16,
+        amplitude: i16,
+        period: u16,
+    },
+    /// Stands still and throws a projectile left at `speed` every
+    /// `interval` frames.
+    Thrower { interval: u16, speed: i16 },
+}
+
+impl EnemyBehavior {
+    pub fn sprite_name(&self) -> &'static str {
+        match self {
+            EnemyBehavior::Walker { .. } => "Walker.png",
+            EnemyBehavior::Flier { .. } => "Flier.png",
+            EnemyBehavior::Thrower { .. } => "Thrower.png",
+        }
+    }
+
+    pub fn projectile_sprite_name(&self) -> Option<&'static str> {
+        match self {
+            EnemyBehavior::Thrower { .. } => Some("Projectile.png"),
+            _ => None,
+        }
+    }
+}
+
+/// Where an enemy has got to relative to where it was placed.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct EnemyMotion {
+    behavior: EnemyBehavior,
+    frame: u16,
+    offset: Point,
+    heading: i16,
+}
+
+impl EnemyMotion {
+    fn new(behavior: EnemyBehavior) -> Self {
+        EnemyMotion {
+            behavior,
+            frame: 0,
+            offset: Point { x: 0, y: 0 },
+            heading: -1,
+        }
+    }
+
+    /// Advances one frame and returns how far the enemy moved.
+    fn update(&mut self) -> Point {
+        match self.behavior {
+            EnemyBehavior::Walker { distance, speed } => {
+                let x = (self.offset.x + self.heading * speed).clamp(-distance, 0);
+                if x == -distance || x == 0 {
+                    self.heading = -self.heading;
+                }
+                let moved = Point {
+                    x: x - self.offset.x,
+                    y: 0,
+                };
+                self.offset.x = x;
+                moved
+            }
+            EnemyBehavior::Flier {
+                speed,
+                amplitude,
+                period,
+            } => {
+                let period = period.max(1);
+                self.frame = (self.frame + 1) % period;
+                let angle = std::f32::consts::TAU * self.frame as f32 / period as f32;
+                let y = (amplitude as f32 * angle.sin()).round() as i16;
+                let moved = Point {
+                    x: -speed,
+                    y: y - self.offset.y,
+                };
+                self.offset = Point {
+                    x: self.offset.x - speed,
+                    y,
+                };
+                moved
+            }
+            EnemyBehavior::Thrower { interval, .. } => {
+                self.frame = (self.frame + 1) % interval.max(1);
+                Point { x: 0, y: 0 }
+            }
+        }
+    }
+
+    /// Walkers are flipped while they pace back to the right.
+    fn facing_right(&self) -> bool {
+        matches!(self.behavior, EnemyBehavior::Walker { .. }) && self.heading > 0
+    }
+
+    /// Whether a thrower lets go of a projectile this frame.
+    fn throws(&self) -> bool {
+        matches!(self.behavior, EnemyBehavior::Thrower { .. }) && self.frame == 0
+    }
+}
+
+/// An enemy without its sprites, so the simulation can move it too.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct EnemyBody {
+    motion: EnemyMotion,
+    bounding_box: Rect,
+    /// The width and height of what it throws, if anything.
+    projectile_size: Option<(i16, i16)>,
+    projectiles: Vec<Rect>,
+    defeated: bool,
+}
+
+impl EnemyBody {
+    fn new(
+        behavior: EnemyBehavior,
+        bounding_box: Rect,
+        projectile_size: Option<(i16, i16)>,
+    ) -> Self {
+        EnemyBody {
+            motion: EnemyMotion::new(behavior),
+            bounding_box,
+            projectile_size,
+            projectiles: vec![],
+            defeated: false,
+        }
+    }
+
+    fn check_intersection(&self, actor: &dyn Actor) -> Option<Collision> {
+        let body = (!self.defeated).then_some(&self.bounding_box);
+        enemy_collision(actor, body, &self.projectiles)
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.bounding_box.set_x(self.bounding_box.x() + x);
+        self.projectiles
+            .iter_mut()
+            .for_each(|projectile| projectile.set_x(projectile.x() + x));
+    }
+
+    /// Enemies wait until they come into view before they move.
+    fn update(&mut self, view: &Rect) {
+        if let EnemyBehavior::Thrower { speed, .. } = self.motion.behavior {
+            self.projectiles
+                .iter_mut()
+                .for_each(|projectile| projectile.set_x(projectile.x() - speed));
+            self.projectiles
+                .retain(|projectile| projectile.right() > view.x());
+        }
+        if self.defeated || self.bounding_box.x() >= view.right() {
+            return;
+        }
+        let moved = self.motion.update();
+        self.bounding_box.set_x(self.bounding_box.x() + moved.x);
+        self.bounding_box.set_y(self.bounding_box.y() + moved.y);
+        if self.motion.throws() {
+            self.throw();
+        }
+    }
+
+    fn landed_on(&mut self) {
+        self.defeated = true;
+    }
+
+    fn throw(&mut self) {
+        if let Some((w, h)) = self.projectile_size {
+            self.projectiles.push(Rect::new_from_x_y(
+                self.bounding_box.x() - w,
+                self.bounding_box.y() + THROW_HEIGHT,
+                w,
+                h,
+            ));
+        }
+    }
+}
+
+pub(crate) struct Enemy {
+    sheet: Rc<SpriteSheet>,
+    sprite: Cell,
+    projectile: Option<Cell>,
+    body: EnemyBody,
+}
+
+impl Enemy {
+    pub(crate) fn new(
+        sheet: Rc<SpriteSheet>,
+        sprite: Cell,
+        projectile: Option<Cell>,
+        behavior: EnemyBehavior,
+        position: Point,
+    ) -> Self {
+        let bounding_box =
+            Rect::new_from_x_y(position.x, position.y, sprite.frame.w, sprite.frame.h);
+        let projectile_size = projectile
+            .as_ref()
+            .map(|projectile| (projectile.frame.w, projectile.frame.h));
+        Enemy {
+            sheet,
+            sprite,
+            projectile,
+            body: EnemyBody::new(behavior, bounding_box, projectile_size),
+        }
+    }
+
+    fn draw_cell(&self, renderer: &Renderer, cell: &Cell, destination: &Rect, params: &DrawParams) {
+        self.sheet
+            .draw_with(
+                renderer,
+                &Rect::new_from_x_y(cell.frame.x, cell.frame.y, cell.frame.w, cell.frame.h),
+                destination,
+                params,
+            )
+            .expect("failed to draw enemy.");
+    }
+}
+
+impl Obstacle for Enemy {
+    fn left(&self) -> i16 {
+        self.body.bounding_box.x()
+    }
+
+    fn right(&self) -> i16 {
+        self.body.bounding_box.right()
+    }
+
+    fn check_intersection(&self, actor: &dyn Actor) -> Option<Collision> {
+        self.body.check_intersection(actor)
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        if !self.body.defeated {
+            let params = DrawParams {
+                flip_horizontally: self.body.motion.facing_right(),
+                ..DrawParams::default()
+            };
+            self.draw_cell(renderer, &self.sprite, &self.body.bounding_box, &params);
+        }
+        if let Some(cell) = &self.projectile {
+            self.body.projectiles.iter().for_each(|projectile| {
+                self.draw_cell(renderer, cell, projectile, &DrawParams::default())
+            });
+        }
+    }
+
+    fn move_horizontally(&mut self, x: i16) {
+        self.body.move_horizontally(x);
+    }
+
+    fn draw_bounding_box(&self, renderer: &Renderer) {
+        if !self.body.defeated {
+            renderer.draw_rect(&self.body.bounding_box, &DEBUG_OUTLINE);
+        }
+        self.body
+            .projectiles
+            .iter()
+            .for_each(|projectile| renderer.draw_rect(projectile, &DEBUG_OUTLINE));
+    }
+
+    fn update(&mut self, view: &Rect) {
+        self.body.update(view);
+    }
+
+    fn landed_on(&mut self) {
+        self.body.landed_on();
+    }
+}
+
 #[derive(Clone)]
 enum RedHatBoyStateMachine {
     Idle(RedHatBoyState<Idle>),
     Running(RedHatBoyState<Running>),
     Sliding(RedHatBoyState<Sliding>),
     Jumping(RedHatBoyState<Jumping>),
+    Airborne(RedHatBoyState<Airborne>),
+    Hurt(RedHatBoyState<Hurt>),
     Falling(RedHatBoyState<Falling>),
     KnockedOut(RedHatBoyState<KnockedOut>),
 }
@@ -663,13 +1988,24 @@ pub enum Event {
     Slide,
     Jump,
     Land(i16),
+    Bounce,
     KnockOut,
+    GainLife,
+    OverPit,
+    Move(i16),
     Update,
 }
 
 impl RedHatBoyStateMachine {
     fn transition(self, event: Event) -> Self {
+        self.next_state(event)
+            .take_buffered_jump()
+            .fall_out_of_bounds()
+    }
+
+    fn next_state(self, event: Event) -> Self {
         match (self.clone(), event) {
+            (_, Event::KnockOut) if self.invulnerable() => self,
             (RedHatBoyStateMachine::Idle(state), Event::Run) => state.run().into(),
             (RedHatBoyStateMachine::Idle(_), Event::Slide) => self,
             (RedHatBoyStateMachine::Idle(_), Event::Jump) => self,
@@ -677,7 +2013,10 @@ impl RedHatBoyStateMachine {
             (RedHatBoyStateMachine::Idle(_), Event::KnockOut) => self,
             (RedHatBoyStateMachine::Running(_), Event::Run) => self,
             (RedHatBoyStateMachine::Running(state), Event::Slide) => state.slide().into(),
-            (RedHatBoyStateMachine::Running(state), Event::Jump) => state.jump().into(),
+            (RedHatBoyStateMachine::Running(state), Event::Jump) if state.can_jump() => {
+                state.jump().into()
+            }
+            (RedHatBoyStateMachine::Running(state), Event::Jump) => state.buffer_jump().into(),
             (RedHatBoyStateMachine::Running(state), Event::Land(position)) => {
                 state.land_on(position).into()
             }
@@ -691,11 +2030,34 @@ impl RedHatBoyStateMachine {
             (RedHatBoyStateMachine::Sliding(state), Event::KnockOut) => state.knock_out().into(),
             (RedHatBoyStateMachine::Jumping(_), Event::Run) => self,
             (RedHatBoyStateMachine::Jumping(_), Event::Slide) => self,
-            (RedHatBoyStateMachine::Jumping(_), Event::Jump) => self,
+            (RedHatBoyStateMachine::Jumping(state), Event::Jump) if state.can_double_jump() => {
+                state.double_jump().into()
+            }
+            (RedHatBoyStateMachine::Jumping(state), Event::Jump) => state.buffer_jump().into(),
             (RedHatBoyStateMachine::Jumping(state), Event::Land(position)) => {
                 state.land_on(position).into()
             }
             (RedHatBoyStateMachine::Jumping(state), Event::KnockOut) => state.knock_out().into(),
+            (RedHatBoyStateMachine::Airborne(_), Event::Run) => self,
+            (RedHatBoyStateMachine::Airborne(_), Event::Slide) => self,
+            (RedHatBoyStateMachine::Airborne(state), Event::Jump) if state.can_jump() => {
+                state.jump().into()
+            }
+            (RedHatBoyStateMachine::Airborne(state), Event::Jump) if state.can_double_jump() => {
+                state.double_jump().into()
+            }
+            (RedHatBoyStateMachine::Airborne(state), Event::Jump) => state.buffer_jump().into(),
+            (RedHatBoyStateMachine::Airborne(state), Event::Land(position)) => {
+                state.land_on(position).into()
+            }
+            (RedHatBoyStateMachine::Airborne(state), Event::KnockOut) => state.knock_out().into(),
+            (RedHatBoyStateMachine::Hurt(_), Event::Run) => self,
+            (RedHatBoyStateMachine::Hurt(_), Event::Slide) => self,
+            (RedHatBoyStateMachine::Hurt(_), Event::Jump) => self,
+            (RedHatBoyStateMachine::Hurt(state), Event::Land(position)) => {
+                state.land_on(position).into()
+            }
+            (RedHatBoyStateMachine::Hurt(_), Event::KnockOut) => self,
             (RedHatBoyStateMachine::Falling(_), Event::Run) => self,
             (RedHatBoyStateMachine::Falling(_), Event::Slide) => self,
             (RedHatBoyStateMachine::Falling(_), Event::Jump) => self,
@@ -706,10 +2068,60 @@ impl RedHatBoyStateMachine {
             (RedHatBoyStateMachine::KnockedOut(_), Event::Jump) => self,
             (RedHatBoyStateMachine::KnockedOut(_), Event::KnockOut) => self,
             (RedHatBoyStateMachine::KnockedOut(_), Event::Land(_)) => self,
+            (RedHatBoyStateMachine::Idle(_), Event::GainLife) => self,
+            (RedHatBoyStateMachine::Running(state), Event::GainLife) => state.gain_life().into(),
+            (RedHatBoyStateMachine::Sliding(state), Event::GainLife) => state.gain_life().into(),
+            (RedHatBoyStateMachine::Jumping(state), Event::GainLife) => state.gain_life().into(),
+            (RedHatBoyStateMachine::Airborne(state), Event::GainLife) => state.gain_life().into(),
+            (RedHatBoyStateMachine::Hurt(state), Event::GainLife) => state.gain_life().into(),
+            (RedHatBoyStateMachine::Falling(_), Event::GainLife) => self,
+            (RedHatBoyStateMachine::KnockedOut(_), Event::GainLife) => self,
+            (RedHatBoyStateMachine::Idle(_), Event::OverPit) => self,
+            (RedHatBoyStateMachine::Running(state), Event::OverPit) => state.over_pit().into(),
+            (RedHatBoyStateMachine::Sliding(state), Event::OverPit) => state.over_pit().into(),
+            (RedHatBoyStateMachine::Jumping(state), Event::OverPit) => state.over_pit().into(),
+            (RedHatBoyStateMachine::Airborne(state), Event::OverPit) => state.over_pit().into(),
+            (RedHatBoyStateMachine::Hurt(state), Event::OverPit) => state.over_pit().into(),
+            (RedHatBoyStateMachine::Falling(_), Event::OverPit) => self,
+            (RedHatBoyStateMachine::KnockedOut(_), Event::OverPit) => self,
+            (RedHatBoyStateMachine::Idle(_), Event::Bounce) => self,
+            (RedHatBoyStateMachine::Running(state), Event::Bounce) => state.bounce().into(),
+            (RedHatBoyStateMachine::Sliding(state), Event::Bounce) => state.bounce().into(),
+            (RedHatBoyStateMachine::Jumping(state), Event::Bounce) => state.bounce().into(),
+            (RedHatBoyStateMachine::Airborne(state), Event::Bounce) => state.bounce().into(),
+            (RedHatBoyStateMachine::Hurt(_), Event::Bounce) => self,
+            (RedHatBoyStateMachine::Falling(_), Event::Bounce) => self,
+            (RedHatBoyStateMachine::KnockedOut(_), Event::Bounce) => self,
+            (RedHatBoyStateMachine::Idle(state), Event::Move(x)) => {
+                state.move_horizontally(x).into()
+            }
+            (RedHatBoyStateMachine::Running(state), Event::Move(x)) => {
+                state.move_horizontally(x).into()
+            }
+            (RedHatBoyStateMachine::Sliding(state), Event::Move(x)) => {
+                state.move_horizontally(x).into()
+            }
+            (RedHatBoyStateMachine::Jumping(state), Event::Move(x)) => {
+                state.move_horizontally(x).into()
+            }
+            (RedHatBoyStateMachine::Airborne(state), Event::Move(x)) => {
+                state.move_horizontally(x).into()
+            }
+            (RedHatBoyStateMachine::Hurt(state), Event::Move(x)) => {
+                state.move_horizontally(x).into()
+            }
+            (RedHatBoyStateMachine::Falling(state), Event::Move(x)) => {
+                state.move_horizontally(x).into()
+            }
+            (RedHatBoyStateMachine::KnockedOut(state), Event::Move(x)) => {
+                state.move_horizontally(x).into()
+            }
             (RedHatBoyStateMachine::Idle(state), Event::Update) => state.update().into(),
             (RedHatBoyStateMachine::Running(state), Event::Update) => state.update().into(),
             (RedHatBoyStateMachine::Sliding(state), Event::Update) => state.update().into(),
             (RedHatBoyStateMachine::Jumping(state), Event::Update) => state.update().into(),
+            (RedHatBoyStateMachine::Airborne(state), Event::Update) => state.update().into(),
+            (RedHatBoyStateMachine::Hurt(state), Event::Update) => state.update().into(),
             (RedHatBoyStateMachine::Falling(state), Event::Update) => state.update().into(),
             (RedHatBoyStateMachine::KnockedOut(_), Event::Update) => self,
         }
@@ -719,12 +2131,36 @@ impl RedHatBoyStateMachine {
         self.transition(Event::Update)
     }
 
+    fn take_buffered_jump(self) -> Self {
+        match self {
+            RedHatBoyStateMachine::Running(state) if state.has_buffered_jump() => {
+                state.jump().into()
+            }
+            _ => self,
+        }
+    }
+
+    /// Falling into a pit ends the run, however many lives are left.
+    fn fall_out_of_bounds(self) -> Self {
+        if self.context().position.y > HEIGHT && !self.knocked_out() {
+            RedHatBoyState::<KnockedOut>::fallen(self.context()).into()
+        } else {
+            self
+        }
+    }
+
+    fn taking_off(&self) -> bool {
+        matches!(self, RedHatBoyStateMachine::Jumping(state) if state.taking_off())
+    }
+
     fn frame_name(&self) -> &str {
         match self {
             RedHatBoyStateMachine::Idle(state) => state.frame_name(),
             RedHatBoyStateMachine::Running(state) => state.frame_name(),
             RedHatBoyStateMachine::Sliding(state) => state.frame_name(),
             RedHatBoyStateMachine::Jumping(state) => state.frame_name(),
+            RedHatBoyStateMachine::Airborne(state) => state.frame_name(),
+            RedHatBoyStateMachine::Hurt(state) => state.frame_name(),
             RedHatBoyStateMachine::Falling(state) => state.frame_name(),
             RedHatBoyStateMachine::KnockedOut(state) => state.frame_name(),
         }
@@ -736,6 +2172,8 @@ impl RedHatBoyStateMachine {
             RedHatBoyStateMachine::Running(state) => state.context(),
             RedHatBoyStateMachine::Sliding(state) => state.context(),
             RedHatBoyStateMachine::Jumping(state) => state.context(),
+            RedHatBoyStateMachine::Airborne(state) => state.context(),
+            RedHatBoyStateMachine::Hurt(state) => state.context(),
             RedHatBoyStateMachine::Falling(state) => state.context(),
             RedHatBoyStateMachine::KnockedOut(state) => state.context(),
         }
@@ -744,6 +2182,50 @@ impl RedHatBoyStateMachine {
     fn knocked_out(&self) -> bool {
         matches!(self, RedHatBoyStateMachine::KnockedOut(_))
     }
+
+    fn invulnerable(&self) -> bool {
+        self.context().invulnerable_frames > 0
+    }
+
+    /// Blinks while the boy is invulnerable after a hit.
+    fn visible(&self) -> bool {
+        (self.context().invulnerable_frames / BLINK_FRAMES) % 2 == 0
+    }
+
+    fn sprite_name(&self) -> String {
+        format!(
+            "{} ({}).png",
+            self.frame_name(),
+            (self.context().frame / 3) + 1,
+        )
+    }
+
+    fn current_sprite<'a>(&self, sheet: &'a Sheet) -> Option<&'a Cell> {
+        sheet.frames.get(&self.sprite_name())
+    }
+
+    fn destination_box(&self, sheet: &Sheet) -> Rect {
+        let sprite = self.current_sprite(sheet).expect("Cell not found");
+
+        Rect::new_from_x_y(
+            self.context().position.x + sprite.sprite_source_size.x,
+            self.context().position.y + sprite.sprite_source_size.y,
+            sprite.frame.w,
+            sprite.frame.h,
+        )
+    }
+
+    fn bounding_box(&self, sheet: &Sheet) -> Rect {
+        const X_OFFSET: f32 = 18.0;
+        const Y_OFFSET: f32 = 14.0;
+        const WIDTH_OFFSET: f32 = 28.0;
+        let mut bounding_box = self.destination_box(sheet);
+        bounding_box.set_x(bounding_box.x() + X_OFFSET as i16);
+        bounding_box.set_y(bounding_box.y() + Y_OFFSET as i16);
+        bounding_box.w -= WIDTH_OFFSET as i16;
+        bounding_box.h -= Y_OFFSET as i16;
+        bounding_box
+    }
 }
 
 impl From<RedHatBoyState<Idle>> for RedHatBoyStateMachine {
@@ -770,6 +2252,30 @@ impl From<RedHatBoyState<Jumping>> for RedHatBoyStateMachine {
     }
 }
 
+impl From<RedHatBoyState<Airborne>> for RedHatBoyStateMachine {
+    fn from(state: RedHatBoyState<Airborne>) -> Self {
+        RedHatBoyStateMachine::Airborne(state)
+    }
+}
+
+impl From<RunningEndState> for RedHatBoyStateMachine {
+    fn from(end_state: RunningEndState) -> Self {
+        match end_state {
+            RunningEndState::Running(running_state) => running_state.into(),
+            RunningEndState::Airborne(airborne_state) => airborne_state.into(),
+        }
+    }
+}
+
+impl From<AirborneEndState> for RedHatBoyStateMachine {
+    fn from(end_state: AirborneEndState) -> Self {
+        match end_state {
+            AirborneEndState::Complete(running_state) => running_state.into(),
+            AirborneEndState::Airborne(airborne_state) => airborne_state.into(),
+        }
+    }
+}
+
 impl From<SlidingEndState> for RedHatBoyStateMachine {
     fn from(end_state: SlidingEndState) -> Self {
         match end_state {
@@ -788,6 +2294,30 @@ impl From<JumpingEndState> for RedHatBoyStateMachine {
     }
 }
 
+impl From<RedHatBoyState<Hurt>> for RedHatBoyStateMachine {
+    fn from(state: RedHatBoyState<Hurt>) -> Self {
+        RedHatBoyStateMachine::Hurt(state)
+    }
+}
+
+impl From<HurtEndState> for RedHatBoyStateMachine {
+    fn from(end_state: HurtEndState) -> Self {
+        match end_state {
+            HurtEndState::Complete(running_state) => running_state.into(),
+            HurtEndState::Hurt(hurt_state) => hurt_state.into(),
+        }
+    }
+}
+
+impl From<HitEndState> for RedHatBoyStateMachine {
+    fn from(end_state: HitEndState) -> Self {
+        match end_state {
+            HitEndState::Hurt(hurt_state) => hurt_state.into(),
+            HitEndState::Falling(falling_state) => falling_state.into(),
+        }
+    }
+}
+
 impl From<RedHatBoyState<Falling>> for RedHatBoyStateMachine {
     fn from(state: RedHatBoyState<Falling>) -> Self {
         RedHatBoyStateMachine::Falling(state)
@@ -810,26 +2340,34 @@ impl From<RedHatBoyState<KnockedOut>> for RedHatBoyStateMachine {
 }
 
 mod red_hat_boy_states {
-    use crate::engine::{Audio, Point, Sound};
+    use crate::engine::Point;
     use crate::game::HEIGHT;
+    use crate::movement::Movement;
 
     const FLOOR: i16 = 479;
     const PLAYER_HEIGHT: i16 = HEIGHT - FLOOR;
-    const STARTING_POINT: i16 = -20;
+    const STARTING_POINT: i16 = 70;
     const IDLE_FRAME_NAME: &str = "Idle";
     const RUN_FRAME_NAME: &str = "Run";
     const SLIDING_FRAME_NAME: &str = "Slide";
     const JUMPING_FRAME_NAME: &str = "Jump";
     const FALLING_FRAME_NAME: &str = "Dead";
+    const HURT_FRAME_NAME: &str = "Hurt";
     const IDLE_FRAMES: u8 = 29;
     const RUNNING_FRAMES: u8 = 23;
     const SLIDING_FRAMES: u8 = 14;
     const JUMPING_FRAMES: u8 = 35;
     const RUNNING_SPEED: i16 = 4;
     const FALLING_FRAMES: u8 = 29;
+    const HURT_FRAMES: u8 = 23;
+    const INVULNERABLE_FRAMES: u8 = 90;
+    const MAX_LIVES: u8 = 5;
     const JUMP_SPEED: i16 = -25;
+    const BOUNCE_SPEED: i16 = -15;
     const GRAVITY: i16 = 1;
     const TERMINAL_VELOCITY: i16 = 20;
+    // Landing comes after the update, so one frame without it is normal.
+    const UNSUPPORTED_FRAMES: u8 = 2;
 
     #[derive(Clone)]
     pub struct RedHatBoyState<S> {
@@ -842,8 +2380,15 @@ mod red_hat_boy_states {
         pub frame: u8,
         pub position: Point,
         pub velocity: Point,
-        pub audio: Audio,
-        pub jump_sound: Sound,
+        pub lives: u8,
+        pub invulnerable_frames: u8,
+        pub movement: Movement,
+        /// Frames since the boy last stood on something.
+        pub airborne_frames: u8,
+        pub jump_buffer: u8,
+        pub air_jumps: u8,
+        /// Set by a pit for the next update, so the floor doesn't catch him.
+        pub over_pit: bool,
     }
 
     impl RedHatBoyContext {
@@ -856,14 +2401,39 @@ mod red_hat_boy_states {
             } else {
                 self.frame = 0;
             }
+            // Once he's below the floor he's in a pit, and can't climb out.
+            let above_floor = self.position.y <= FLOOR;
             self.position.y += self.velocity.y;
-            if self.position.y > FLOOR {
-                self.position.y = FLOOR
+            if self.position.y >= FLOOR && above_floor && !self.over_pit {
+                self.position.y = FLOOR;
+                self = self.ground();
+            } else {
+                self.airborne_frames = self.airborne_frames.saturating_add(1);
             }
+            self.over_pit = false;
+            self.invulnerable_frames = self.invulnerable_frames.saturating_sub(1);
+            self.jump_buffer = self.jump_buffer.saturating_sub(1);
 
             self
         }
 
+        fn ground(mut self) -> Self {
+            self.velocity.y = 0;
+            self.airborne_frames = 0;
+            self.air_jumps = self.movement.air_jumps();
+            self
+        }
+
+        fn take_off(mut self) -> Self {
+            self.jump_buffer = 0;
+            self.set_vertical_velocity(JUMP_SPEED)
+        }
+
+        /// Only meaningful straight after `update`.
+        fn on_floor(&self) -> bool {
+            self.airborne_frames == 0
+        }
+
         fn reset_frame(mut self) -> Self {
             self.frame = 0;
             self
@@ -882,7 +2452,7 @@ mod red_hat_boy_states {
         fn set_on(mut self, position: i16) -> Self {
             let position = position - PLAYER_HEIGHT;
             self.position.y = position;
-            self
+            self.ground()
         }
 
         fn stop(mut self) -> Self {
@@ -891,10 +2461,14 @@ mod red_hat_boy_states {
             self
         }
 
-        fn play_jump_sound(self) -> Self {
-            if let Err(err) = self.audio.play_sound(&self.jump_sound) {
-                log!("Error playing jump sound {:#?}", err);
-            }
+        fn lose_life(mut self) -> Self {
+            self.lives = self.lives.saturating_sub(1);
+            self.invulnerable_frames = INVULNERABLE_FRAMES;
+            self
+        }
+
+        fn gain_life(mut self) -> Self {
+            self.lives = (self.lives + 1).min(MAX_LIVES);
             self
         }
     }
@@ -908,6 +2482,10 @@ mod red_hat_boy_states {
     #[derive(Copy, Clone)]
     pub struct Jumping;
     #[derive(Copy, Clone)]
+    pub struct Airborne;
+    #[derive(Copy, Clone)]
+    pub struct Hurt;
+    #[derive(Copy, Clone)]
     pub struct Falling;
     #[derive(Copy, Clone)]
     pub struct KnockedOut;
@@ -916,6 +2494,58 @@ mod red_hat_boy_states {
         pub fn context(&self) -> &RedHatBoyContext {
             &self.context
         }
+
+        pub fn gain_life(mut self) -> Self {
+            self.context = self.context.gain_life();
+            self
+        }
+
+        pub fn over_pit(mut self) -> Self {
+            self.context.over_pit = true;
+            self
+        }
+
+        pub fn move_horizontally(mut self, x: i16) -> Self {
+            self.context.position.x += x;
+            self
+        }
+
+        /// Springs back up off an enemy he stomped on.
+        pub fn bounce(self) -> RedHatBoyState<Jumping> {
+            RedHatBoyState {
+                context: self
+                    .context
+                    .reset_frame()
+                    .set_vertical_velocity(BOUNCE_SPEED),
+                _state: Jumping {},
+            }
+        }
+
+        /// Remembers a jump the boy can't make yet, in case he lands soon.
+        pub fn buffer_jump(mut self) -> Self {
+            self.context.jump_buffer = self.context.movement.jump_buffer_frames;
+            self
+        }
+
+        /// Costs a life, and only falls over when it was the last one.
+        fn take_hit(self) -> HitEndState {
+            if self.context.lives > 1 {
+                HitEndState::Hurt(RedHatBoyState {
+                    context: self.context.reset_frame().lose_life(),
+                    _state: Hurt {},
+                })
+            } else {
+                HitEndState::Falling(RedHatBoyState {
+                    context: self.context.reset_frame().stop().lose_life(),
+                    _state: Falling {},
+                })
+            }
+        }
+    }
+
+    pub enum HitEndState {
+        Hurt(RedHatBoyState<Hurt>),
+        Falling(RedHatBoyState<Falling>),
     }
 
     impl RedHatBoyState<Idle> {
@@ -928,7 +2558,7 @@ mod red_hat_boy_states {
             IDLE_FRAME_NAME
         }
 
-        pub fn new(audio: Audio, jump_sound: Sound) -> Self {
+        pub fn new(lives: u8, movement: Movement) -> Self {
             RedHatBoyState {
                 context: RedHatBoyContext {
                     frame: 0,
@@ -937,8 +2567,13 @@ mod red_hat_boy_states {
                         y: FLOOR,
                     },
                     velocity: Point { x: 0, y: 0 },
-                    audio,
-                    jump_sound,
+                    lives: lives.clamp(1, MAX_LIVES),
+                    invulnerable_frames: 0,
+                    movement,
+                    airborne_frames: 0,
+                    jump_buffer: 0,
+                    air_jumps: movement.air_jumps(),
+                    over_pit: false,
                 },
                 _state: Idle,
             }
@@ -953,9 +2588,21 @@ mod red_hat_boy_states {
     }
 
     impl RedHatBoyState<Running> {
-        pub fn update(mut self) -> Self {
+        pub fn update(mut self) -> RunningEndState {
             self.context = self.context.update(RUNNING_FRAMES);
-            self
+
+            if self.context.airborne_frames > UNSUPPORTED_FRAMES {
+                RunningEndState::Airborne(self.fall())
+            } else {
+                RunningEndState::Running(self)
+            }
+        }
+
+        fn fall(self) -> RedHatBoyState<Airborne> {
+            RedHatBoyState {
+                context: self.context.reset_frame(),
+                _state: Airborne {},
+            }
         }
 
         pub fn frame_name(&self) -> &str {
@@ -969,13 +2616,18 @@ mod red_hat_boy_states {
             }
         }
 
+        /// He may already be past the edge of a platform, before he falls.
+        pub fn can_jump(&self) -> bool {
+            self.context.airborne_frames <= self.context.movement.coyote_frames
+        }
+
+        pub fn has_buffered_jump(&self) -> bool {
+            self.context.jump_buffer > 0 && self.context.airborne_frames == 0
+        }
+
         pub fn jump(self) -> RedHatBoyState<Jumping> {
             RedHatBoyState {
-                context: self
-                    .context
-                    .set_vertical_velocity(JUMP_SPEED)
-                    .reset_frame()
-                    .play_jump_sound(),
+                context: self.context.take_off().reset_frame(),
                 _state: Jumping {},
             }
         }
@@ -987,14 +2639,16 @@ mod red_hat_boy_states {
             }
         }
 
-        pub fn knock_out(self) -> RedHatBoyState<Falling> {
-            RedHatBoyState {
-                context: self.context.reset_frame().stop(),
-                _state: Falling {},
-            }
+        pub fn knock_out(self) -> HitEndState {
+            self.take_hit()
         }
     }
 
+    pub enum RunningEndState {
+        Running(RedHatBoyState<Running>),
+        Airborne(RedHatBoyState<Airborne>),
+    }
+
     impl RedHatBoyState<Sliding> {
         pub fn update(mut self) -> SlidingEndState {
             self.context = self.context.update(SLIDING_FRAMES);
@@ -1007,69 +2661,168 @@ mod red_hat_boy_states {
         }
 
         pub fn frame_name(&self) -> &str {
-            SLIDING_FRAME_NAME
+            SLIDING_FRAME_NAME
+        }
+
+        pub fn stand(self) -> RedHatBoyState<Running> {
+            RedHatBoyState {
+                context: self.context.reset_frame(),
+                _state: Running {},
+            }
+        }
+
+        pub fn land_on(self, position: i16) -> RedHatBoyState<Sliding> {
+            RedHatBoyState {
+                context: self.context.set_on(position),
+                _state: Sliding {},
+            }
+        }
+
+        pub fn knock_out(self) -> HitEndState {
+            self.take_hit()
+        }
+    }
+
+    pub enum SlidingEndState {
+        Complete(RedHatBoyState<Running>),
+        Sliding(RedHatBoyState<Sliding>),
+    }
+
+    impl RedHatBoyState<Jumping> {
+        pub fn update(mut self) -> JumpingEndState {
+            self.context = self.context.update(JUMPING_FRAMES);
+
+            if self.context.on_floor() {
+                JumpingEndState::Complete(self.land_on(HEIGHT))
+            } else {
+                JumpingEndState::Jumping(self)
+            }
+        }
+
+        pub fn frame_name(&self) -> &str {
+            JUMPING_FRAME_NAME
+        }
+
+        pub fn can_double_jump(&self) -> bool {
+            self.context.air_jumps > 0
+        }
+
+        pub fn double_jump(mut self) -> RedHatBoyState<Jumping> {
+            self.context.air_jumps -= 1;
+            RedHatBoyState {
+                context: self.context.take_off().reset_frame(),
+                _state: Jumping {},
+            }
+        }
+
+        /// Whether the boy has only just pushed off.
+        pub fn taking_off(&self) -> bool {
+            self.context.velocity.y == JUMP_SPEED
+        }
+
+        pub fn land_on(self, position: i16) -> RedHatBoyState<Running> {
+            RedHatBoyState {
+                context: self.context.reset_frame().set_on(position),
+                _state: Running {},
+            }
+        }
+
+        pub fn knock_out(self) -> HitEndState {
+            self.take_hit()
+        }
+    }
+
+    pub enum JumpingEndState {
+        Complete(RedHatBoyState<Running>),
+        Jumping(RedHatBoyState<Jumping>),
+    }
+
+    impl RedHatBoyState<Airborne> {
+        pub fn update(mut self) -> AirborneEndState {
+            self.context = self.context.update(JUMPING_FRAMES);
+
+            if self.context.on_floor() {
+                AirborneEndState::Complete(self.land_on(HEIGHT))
+            } else {
+                AirborneEndState::Airborne(self)
+            }
+        }
+
+        pub fn frame_name(&self) -> &str {
+            JUMPING_FRAME_NAME
         }
 
-        pub fn stand(self) -> RedHatBoyState<Running> {
-            RedHatBoyState {
-                context: self.context.reset_frame(),
-                _state: Running {},
-            }
+        /// Still true for a few frames after running off a platform.
+        pub fn can_jump(&self) -> bool {
+            self.context.airborne_frames <= self.context.movement.coyote_frames
         }
 
-        pub fn land_on(self, position: i16) -> RedHatBoyState<Sliding> {
+        pub fn jump(self) -> RedHatBoyState<Jumping> {
             RedHatBoyState {
-                context: self.context.set_on(position),
-                _state: Sliding {},
+                context: self.context.take_off().reset_frame(),
+                _state: Jumping {},
             }
         }
 
-        pub fn knock_out(self) -> RedHatBoyState<Falling> {
+        pub fn can_double_jump(&self) -> bool {
+            self.context.air_jumps > 0
+        }
+
+        pub fn double_jump(mut self) -> RedHatBoyState<Jumping> {
+            self.context.air_jumps -= 1;
+            self.jump()
+        }
+
+        pub fn land_on(self, position: i16) -> RedHatBoyState<Running> {
             RedHatBoyState {
-                context: self.context.reset_frame().stop(),
-                _state: Falling {},
+                context: self.context.reset_frame().set_on(position),
+                _state: Running {},
             }
         }
+
+        pub fn knock_out(self) -> HitEndState {
+            self.take_hit()
+        }
     }
 
-    pub enum SlidingEndState {
+    pub enum AirborneEndState {
         Complete(RedHatBoyState<Running>),
-        Sliding(RedHatBoyState<Sliding>),
+        Airborne(RedHatBoyState<Airborne>),
     }
 
-    impl RedHatBoyState<Jumping> {
-        pub fn update(mut self) -> JumpingEndState {
-            self.context = self.context.update(JUMPING_FRAMES);
+    impl RedHatBoyState<Hurt> {
+        pub fn update(mut self) -> HurtEndState {
+            self.context = self.context.update(HURT_FRAMES);
 
-            if self.context.position.y >= FLOOR {
-                JumpingEndState::Complete(self.land_on(HEIGHT))
+            if self.context.frame >= HURT_FRAMES {
+                HurtEndState::Complete(self.recover())
             } else {
-                JumpingEndState::Jumping(self)
+                HurtEndState::Hurt(self)
             }
         }
 
         pub fn frame_name(&self) -> &str {
-            JUMPING_FRAME_NAME
+            HURT_FRAME_NAME
         }
 
-        pub fn land_on(self, position: i16) -> RedHatBoyState<Running> {
+        pub fn land_on(self, position: i16) -> RedHatBoyState<Hurt> {
             RedHatBoyState {
                 context: self.context.set_on(position),
-                _state: Running {},
+                _state: Hurt {},
             }
         }
 
-        pub fn knock_out(self) -> RedHatBoyState<Falling> {
+        fn recover(self) -> RedHatBoyState<Running> {
             RedHatBoyState {
-                context: self.context.reset_frame().stop(),
-                _state: Falling {},
+                context: self.context.reset_frame(),
+                _state: Running {},
             }
         }
     }
 
-    pub enum JumpingEndState {
+    pub enum HurtEndState {
         Complete(RedHatBoyState<Running>),
-        Jumping(RedHatBoyState<Jumping>),
+        Hurt(RedHatBoyState<Hurt>),
     }
 
     impl RedHatBoyState<Falling> {
@@ -1104,6 +2857,15 @@ mod red_hat_boy_states {
         pub fn frame_name(&self) -> &str {
             FALLING_FRAME_NAME
         }
+
+        pub fn fallen(context: &RedHatBoyContext) -> Self {
+            let mut context = context.clone().stop();
+            context.lives = 0;
+            RedHatBoyState {
+                context,
+                _state: KnockedOut {},
+            }
+        }
     }
 }
 
@@ -1116,6 +2878,7 @@ fn rightmost(obstacle_list: &Vec<Box<dyn Obstacle>>) -> i16 {
 }
 
 #[cfg(test)]
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
 mod tests {
     use super::*;
     use futures::channel::mpsc::unbounded;
@@ -1134,30 +2897,55 @@ mod tests {
         let sound = Sound {
             buffer: AudioBuffer::new(&options).unwrap(),
         };
-        let rhb = RedHatBoy::new(
+        let dog = Dog::new(
             Sheet {
                 frames: HashMap::new(),
             },
             image.clone(),
-            audio,
-            sound,
+            audio.clone(),
+            sound.clone(),
         );
-        let sprite_sheet = SpriteSheet::new(
+        let rhb = RedHatBoy::new(
             Sheet {
                 frames: HashMap::new(),
             },
             image.clone(),
+            audio.clone(),
+            sound.clone(),
+            1,
+            Movement::default(),
         );
+        let sprite_sheet = Rc::new(SpriteSheet::new(
+            Sheet {
+                frames: HashMap::new(),
+            },
+            image.clone(),
+        ));
         let walk = Walk {
             boy: rhb,
-            backgrounds: [
-                Image::new(image.clone(), Point { x: 0, y: 0 }),
-                Image::new(image.clone(), Point { x: 0, y: 0 }),
-            ],
+            dog,
+            camera: new_camera(),
+            effects: Effects::new(0),
+            font: BitmapFont::new(
+                &serde_json::from_str(include_str!("../static/font.json")).unwrap(),
+                image.clone(),
+            ),
+            background: ParallaxBackground::empty(WIDTH),
+            world: World::new(),
+            systems: walk_systems(),
             obstacles: vec![],
-            obstacle_sheet: Rc::new(sprite_sheet),
-            stone: image.clone(),
+            segment_assets: SegmentAssets {
+                images: HashMap::from([("Stone.png".into(), image.clone())]),
+                tiles: sprite_sheet.clone(),
+                pickups: sprite_sheet.clone(),
+                enemies: sprite_sheet,
+            },
+            audio,
+            pickup_sound: sound,
             timeline: 0,
+            score: Score::new(),
+            difficulty: serde_json::from_str(include_str!("../static/difficulty.json")).unwrap(),
+            segments: serde_json::from_str(include_str!("../static/segments.json")).unwrap(),
         };
 
         let document = browser::document().unwrap();
@@ -1170,6 +2958,7 @@ mod tests {
         let state = WalkTheDogState {
             _state: GameOver {
                 new_game_event: receiver,
+                score: Score::new(),
             },
             walk,
         };
@@ -1178,4 +2967,581 @@ mod tests {
         let ui = browser::find_html_element_by_id("ui").unwrap();
         assert_eq!(ui.child_element_count(), 0);
     }
+
+    fn pickup_at(world: &mut World<Tag>, x: i16) -> Entity {
+        let pickup = world.spawn(Tag::Pickup {
+            pickup: Pickup::Coin,
+            spin: 0,
+        });
+        world.positions.insert(pickup, Point { x, y: 400 });
+        world
+            .colliders
+            .insert(pickup, Rect::new_from_x_y(0, 0, 30, 30));
+        pickup
+    }
+
+    #[test]
+    fn pickups_are_mirrored_half_way_round() {
+        let mut world = World::new();
+        let pickup = pickup_at(&mut world, 0);
+
+        (0..PICKUP_SPIN_FRAMES / 2).for_each(|_| spin_pickups(&mut world));
+
+        let scale_x = world.params.get(pickup).map(|params| params.scale_x);
+        assert!(scale_x.is_some_and(|scale_x| (scale_x + 1.0).abs() < 0.01));
+    }
+
+    #[test]
+    fn pickups_are_despawned_once_the_view_has_passed_them() {
+        let mut world = World::new();
+        let passed = pickup_at(&mut world, 100);
+        let ahead = pickup_at(&mut world, 200);
+
+        despawn_passed_pickups(&mut world, 150);
+
+        assert!(world.tags.get(passed).is_none());
+        assert!(world.tags.get(ahead).is_some());
+    }
+
+    #[test]
+    fn game_over_ui_highlights_the_latest_score() {
+        let mut high_scores = HighScores::default();
+        let mut score = Score::new();
+        high_scores.insert(&score);
+        score.clear_obstacles(1);
+        let rank = high_scores.insert(&score);
+
+        let html = game_over_ui(&high_scores, rank);
+
+        assert!(html.contains("<button id='new_game'>New Game</button>"));
+        assert!(html.contains("<li class='current'>10 pts 0m</li><li>0 pts 0m</li>"));
+    }
+
+    struct TestActor {
+        bounding_box: Rect,
+        velocity_y: i16,
+    }
+
+    impl Actor for TestActor {
+        fn bounding_box(&self) -> Rect {
+            Rect::new_from_x_y(
+                self.bounding_box.x(),
+                self.bounding_box.y(),
+                self.bounding_box.w,
+                self.bounding_box.h,
+            )
+        }
+
+        fn pos_y(&self) -> i16 {
+            self.bounding_box.y()
+        }
+
+        fn velocity_y(&self) -> i16 {
+            self.velocity_y
+        }
+    }
+
+    fn actor_at(x: i16, y: i16, velocity_y: i16) -> TestActor {
+        TestActor {
+            bounding_box: Rect::new_from_x_y(x, y, 50, 100),
+            velocity_y,
+        }
+    }
+
+    #[test]
+    fn barriers_knock_out_actors_that_touch_them() {
+        let stone = Rect::new_from_x_y(100, 500, 90, 54);
+
+        assert_eq!(
+            barrier_collision(&actor_at(80, 420, 0), &stone),
+            Some(Collision::KnockOut)
+        );
+        assert_eq!(barrier_collision(&actor_at(300, 420, 0), &stone), None);
+    }
+
+    #[test]
+    fn actors_falling_onto_a_platform_land_on_it() {
+        let platform = [Rect::new_from_x_y(100, 400, 200, 30)];
+
+        assert_eq!(
+            platform_collision(&actor_at(150, 320, 5), 400, &platform),
+            Some(Collision::Land(400))
+        );
+    }
+
+    #[test]
+    fn actors_running_into_a_platform_are_knocked_out() {
+        let platform = [Rect::new_from_x_y(100, 400, 200, 30)];
+
+        assert_eq!(
+            platform_collision(&actor_at(80, 420, 0), 400, &platform),
+            Some(Collision::KnockOut)
+        );
+        assert_eq!(
+            platform_collision(&actor_at(80, 320, -5), 400, &platform),
+            Some(Collision::KnockOut)
+        );
+    }
+    #[test]
+    fn actors_falling_onto_a_one_way_platform_land_on_it() {
+        let platform = [Rect::new_from_x_y(100, 400, 200, 30)];
+
+        assert_eq!(
+            one_way_collision(&actor_at(150, 305, 5), &platform),
+            Some(Collision::Land(400))
+        );
+    }
+
+    #[test]
+    fn actors_pass_through_one_way_platforms_from_below_and_the_side() {
+        let platform = [Rect::new_from_x_y(100, 400, 200, 30)];
+
+        assert_eq!(one_way_collision(&actor_at(150, 350, -10), &platform), None);
+        assert_eq!(one_way_collision(&actor_at(150, 340, 3), &platform), None);
+        assert_eq!(one_way_collision(&actor_at(80, 380, 0), &platform), None);
+    }
+
+    #[test]
+    fn oscillating_platforms_stay_within_their_amplitude() {
+        let mut motion = PlatformMotion::new(PlatformBehavior::Oscillating {
+            amplitude: 30,
+            period: 120,
+        });
+
+        let mut offset_y = 0;
+        (0..120).for_each(|_| {
+            offset_y += motion.update();
+            assert!(offset_y.abs() <= 30);
+        });
+
+        assert_eq!(offset_y, 0);
+    }
+
+    #[test]
+    fn crumbling_platforms_fall_after_being_landed_on() {
+        let mut motion = PlatformMotion::new(PlatformBehavior::Crumbling { delay: 10 });
+        (0..100).for_each(|_| assert_eq!(motion.update(), 0));
+
+        motion.land();
+        (0..10).for_each(|_| assert_eq!(motion.update(), 0));
+        assert!(motion.solid());
+
+        assert!(motion.update() > 0);
+        assert!(!motion.solid());
+    }
+
+    #[test]
+    fn landing_again_does_not_restart_the_crumble() {
+        let mut motion = PlatformMotion::new(PlatformBehavior::Crumbling { delay: 10 });
+        motion.land();
+        (0..5).for_each(|_| {
+            motion.update();
+        });
+
+        motion.land();
+        (0..5).for_each(|_| {
+            motion.update();
+        });
+
+        assert!(motion.update() > 0);
+    }
+
+    #[test]
+    fn static_platforms_never_move() {
+        let mut motion = PlatformMotion::new(PlatformBehavior::Static);
+        motion.land();
+
+        (0..200).for_each(|_| assert_eq!(motion.update(), 0));
+        assert!(motion.solid());
+    }
+
+    #[test]
+    fn coming_down_on_an_enemy_stomps_on_it() {
+        let walker = Rect::new_from_x_y(100, 500, 52, 44);
+
+        assert_eq!(
+            enemy_collision(&actor_at(110, 404, 5), Some(&walker), &[]),
+            Some(Collision::Stomp)
+        );
+    }
+
+    #[test]
+    fn running_into_an_enemy_on_a_platform_knocks_out() {
+        // Standing on a platform at 420 leaves a velocity of 1 after every
+        // update, but his feet were never above the walker.
+        let walker = Rect::new_from_x_y(710, 376, 52, 44);
+
+        assert_eq!(
+            enemy_collision(&actor_at(670, 321, 1), Some(&walker), &[]),
+            Some(Collision::KnockOut)
+        );
+    }
+
+    #[test]
+    fn stomping_on_an_enemy_bounces_the_boy() {
+        let falling = updated(running_boy(1).transition(Event::Jump), 30);
+        assert!(falling.context().velocity.y > 0);
+
+        let boy = falling.transition(Event::Bounce);
+
+        assert!(matches!(boy, RedHatBoyStateMachine::Jumping(_)));
+        assert!(boy.context().velocity.y < 0);
+        assert!(!boy.taking_off());
+    }
+
+    #[test]
+    fn running_into_an_enemy_or_its_projectiles_knocks_out() {
+        let walker = Rect::new_from_x_y(100, 500, 52, 44);
+        let projectile = [Rect::new_from_x_y(300, 520, 20, 20)];
+
+        assert_eq!(
+            enemy_collision(&actor_at(80, 480, 0), Some(&walker), &[]),
+            Some(Collision::KnockOut)
+        );
+        assert_eq!(
+            enemy_collision(&actor_at(290, 480, 0), None, &projectile),
+            Some(Collision::KnockOut)
+        );
+        assert_eq!(enemy_collision(&actor_at(80, 480, 0), None, &[]), None);
+    }
+
+    #[test]
+    fn actors_entirely_over_a_gap_are_over_a_pit() {
+        let gap = Rect::new_from_x_y(100, HEIGHT, 150, GROUND_DEPTH);
+
+        assert_eq!(
+            pit_collision(&actor_at(120, 480, 0), &gap),
+            Some(Collision::OverPit)
+        );
+        assert_eq!(pit_collision(&actor_at(80, 480, 0), &gap), None);
+        assert_eq!(pit_collision(&actor_at(220, 480, 0), &gap), None);
+    }
+
+    #[test]
+    fn walkers_pace_back_and_forth() {
+        let mut motion = EnemyMotion::new(EnemyBehavior::Walker {
+            distance: 100,
+            speed: 4,
+        });
+
+        let mut x = 0;
+        let offsets: Vec<i16> = (0..100)
+            .map(|_| {
+                x += motion.update().x;
+                x
+            })
+            .collect();
+
+        assert_eq!(offsets[24], -100);
+        assert_eq!(offsets[49], 0);
+        assert!(offsets.iter().all(|x| (-100..=0).contains(x)));
+    }
+
+    #[test]
+    fn walkers_face_the_way_they_pace() {
+        let mut motion = EnemyMotion::new(EnemyBehavior::Walker {
+            distance: 100,
+            speed: 4,
+        });
+
+        assert!(!motion.facing_right());
+        (0..25).for_each(|_| {
+            motion.update();
+        });
+        assert!(motion.facing_right());
+        (0..25).for_each(|_| {
+            motion.update();
+        });
+        assert!(!motion.facing_right());
+    }
+
+    #[test]
+    fn fliers_bob_as_they_fly_left() {
+        let mut motion = EnemyMotion::new(EnemyBehavior::Flier {
+            speed: 3,
+            amplitude: 40,
+            period: 60,
+        });
+
+        let mut offset = Point { x: 0, y: 0 };
+        (0..60).for_each(|_| {
+            let moved = motion.update();
+            offset.x += moved.x;
+            offset.y += moved.y;
+            assert!(offset.y.abs() <= 40);
+        });
+
+        assert_eq!((offset.x, offset.y), (-180, 0));
+    }
+
+    #[test]
+    fn throwers_throw_once_every_interval() {
+        let mut motion = EnemyMotion::new(EnemyBehavior::Thrower {
+            interval: 30,
+            speed: 6,
+        });
+
+        let throws = (0..90)
+            .filter(|_| {
+                motion.update();
+                motion.throws()
+            })
+            .count();
+
+        assert_eq!(throws, 3);
+    }
+
+    fn running_boy(lives: u8) -> RedHatBoyStateMachine {
+        RedHatBoyStateMachine::Idle(RedHatBoyState::new(lives, Movement::default()))
+            .transition(Event::Run)
+    }
+
+    #[test]
+    fn a_hit_costs_a_life_when_the_boy_has_one_to_spare() {
+        let boy = running_boy(3).transition(Event::KnockOut);
+
+        assert!(matches!(boy, RedHatBoyStateMachine::Hurt(_)));
+        assert_eq!(boy.context().lives, 2);
+        assert!(boy.invulnerable());
+    }
+
+    #[test]
+    fn a_hit_on_the_last_life_knocks_the_boy_out() {
+        let boy = running_boy(1).transition(Event::KnockOut);
+
+        assert!(matches!(boy, RedHatBoyStateMachine::Falling(_)));
+        assert_eq!(boy.context().lives, 0);
+    }
+
+    #[test]
+    fn hits_are_ignored_while_invulnerable() {
+        let mut boy = running_boy(3).transition(Event::KnockOut);
+        (0..30).for_each(|_| boy = boy.clone().update());
+        assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+
+        let boy = boy.transition(Event::KnockOut);
+
+        assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+        assert_eq!(boy.context().lives, 2);
+    }
+
+    #[test]
+    fn the_boy_can_be_hit_again_once_invulnerability_wears_off() {
+        let mut boy = running_boy(3).transition(Event::KnockOut);
+        (0..200).for_each(|_| boy = boy.clone().update());
+
+        let boy = boy.transition(Event::KnockOut);
+
+        assert!(matches!(boy, RedHatBoyStateMachine::Hurt(_)));
+        assert_eq!(boy.context().lives, 1);
+    }
+
+    #[test]
+    fn the_boy_blinks_while_invulnerable() {
+        let mut boy = running_boy(3).transition(Event::KnockOut);
+        let mut visibility = vec![];
+        (0..20).for_each(|_| {
+            boy = boy.clone().update();
+            visibility.push(boy.visible());
+        });
+
+        assert!(visibility.contains(&true));
+        assert!(visibility.contains(&false));
+    }
+
+    #[test]
+    fn hearts_add_a_life_up_to_the_limit() {
+        let boy = running_boy(1).transition(Event::GainLife);
+        assert_eq!(boy.context().lives, 2);
+
+        let boy = (0..10).fold(boy, |boy, _| boy.transition(Event::GainLife));
+
+        assert_eq!(boy.context().lives, 5);
+    }
+
+    fn assisted_boy(movement: Movement) -> RedHatBoyStateMachine {
+        RedHatBoyStateMachine::Idle(RedHatBoyState::new(1, movement)).transition(Event::Run)
+    }
+
+    fn updated(boy: RedHatBoyStateMachine, frames: usize) -> RedHatBoyStateMachine {
+        (0..frames).fold(boy, |boy, _| boy.update())
+    }
+
+    // A jump from the floor lands again on its 50th update.
+    const JUMP_LENGTH: usize = 50;
+
+    #[test]
+    fn jumps_pressed_just_before_landing_go_off_on_landing() {
+        let movement = Movement {
+            jump_buffer_frames: 8,
+            ..Movement::default()
+        };
+        let boy = updated(
+            assisted_boy(movement).transition(Event::Jump),
+            JUMP_LENGTH - 4,
+        );
+
+        let boy = updated(boy.transition(Event::Jump), 4);
+
+        assert!(boy.taking_off());
+    }
+
+    #[test]
+    fn jumps_pressed_in_the_air_are_dropped_without_a_buffer() {
+        let boy = updated(
+            assisted_boy(Movement::default()).transition(Event::Jump),
+            JUMP_LENGTH - 4,
+        );
+
+        let boy = updated(boy.transition(Event::Jump), 4);
+
+        assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+    }
+
+    #[test]
+    fn the_boy_can_jump_just_after_running_off_a_platform() {
+        let movement = Movement {
+            coyote_frames: 6,
+            ..Movement::default()
+        };
+        let on_platform = assisted_boy(movement).transition(Event::Land(400));
+
+        let early = updated(on_platform.clone(), 3).transition(Event::Jump);
+        let late = updated(on_platform, 10).transition(Event::Jump);
+
+        assert!(early.taking_off());
+        assert!(matches!(late, RedHatBoyStateMachine::Airborne(_)));
+    }
+
+    #[test]
+    fn double_jumps_push_off_once_in_the_air() {
+        let movement = Movement {
+            double_jump: true,
+            ..Movement::default()
+        };
+        let boy = updated(assisted_boy(movement).transition(Event::Jump), 10);
+
+        let boy = boy.transition(Event::Jump);
+        assert!(boy.taking_off());
+
+        let boy = updated(boy, 10).transition(Event::Jump);
+        assert!(!boy.taking_off());
+    }
+
+    #[test]
+    fn landing_gives_the_double_jump_back() {
+        let movement = Movement {
+            double_jump: true,
+            ..Movement::default()
+        };
+        let boy = updated(assisted_boy(movement).transition(Event::Jump), 10)
+            .transition(Event::Jump)
+            .transition(Event::Land(400));
+
+        let boy = updated(boy.transition(Event::Jump), 10).transition(Event::Jump);
+
+        assert!(boy.taking_off());
+    }
+
+    #[test]
+    fn there_is_no_double_jump_unless_configured() {
+        let boy = updated(
+            assisted_boy(Movement::default()).transition(Event::Jump),
+            10,
+        );
+
+        assert!(!boy.transition(Event::Jump).taking_off());
+    }
+
+    #[test]
+    fn running_off_a_platform_drops_the_boy_to_the_floor() {
+        let on_platform = running_boy(1).transition(Event::Land(400));
+
+        let falling = updated(on_platform, 3);
+        assert!(matches!(falling, RedHatBoyStateMachine::Airborne(_)));
+
+        let landed = updated(falling, 30);
+        assert!(matches!(landed, RedHatBoyStateMachine::Running(_)));
+        assert_eq!(
+            landed.context().position.y,
+            running_boy(1).context().position.y
+        );
+    }
+
+    #[test]
+    fn a_running_boy_stays_on_a_platform_that_keeps_landing_him() {
+        let boy = (0..30).fold(running_boy(1), |boy, _| {
+            boy.update().transition(Event::Land(400))
+        });
+
+        assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+    }
+
+    #[test]
+    fn the_boy_lands_back_on_a_platform_he_drops_onto() {
+        let falling = updated(running_boy(1).transition(Event::Land(300)), 3);
+
+        let boy = falling.transition(Event::Land(400));
+
+        assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+    }
+
+    #[test]
+    fn running_over_a_pit_drops_the_boy_out_of_the_game() {
+        let boy = (0..60).fold(running_boy(3), |boy, _| {
+            boy.transition(Event::OverPit).update()
+        });
+
+        assert!(matches!(boy, RedHatBoyStateMachine::KnockedOut(_)));
+        assert_eq!(boy.context().lives, 0);
+    }
+
+    #[test]
+    fn jumping_across_a_pit_lands_on_the_far_side() {
+        let boy = (0..20).fold(running_boy(1).transition(Event::Jump), |boy, _| {
+            boy.transition(Event::OverPit).update()
+        });
+
+        let boy = updated(boy, JUMP_LENGTH);
+
+        assert!(matches!(boy, RedHatBoyStateMachine::Running(_)));
+        assert_eq!(
+            boy.context().position.y,
+            running_boy(1).context().position.y
+        );
+    }
+
+    #[test]
+    fn moving_the_boy_through_the_world_keeps_what_he_is_doing() {
+        let jumping = updated(running_boy(1).transition(Event::Jump), 10);
+        let start = jumping.context().position;
+
+        let moved = jumping.transition(Event::Move(250));
+
+        assert!(matches!(moved, RedHatBoyStateMachine::Jumping(_)));
+        assert_eq!(moved.context().position.x, start.x + 250);
+        assert_eq!(moved.context().position.y, start.y);
+    }
+
+    #[test]
+    fn a_hit_freezes_the_game_and_shakes_the_screen() {
+        let mut effects = Effects::new(1);
+
+        Impact::Hit.apply(&mut effects);
+
+        let frozen = (0..10).filter(|_| effects.take_frozen_frame()).count();
+        assert_eq!(frozen, usize::from(HIT_STOP_FRAMES));
+        effects.update();
+        let shake = effects.shake();
+        assert_ne!((shake.x, shake.y), (0, 0));
+    }
+
+    #[test]
+    fn falling_into_a_pit_shakes_without_freezing() {
+        let mut effects = Effects::new(1);
+
+        Impact::Fall.apply(&mut effects);
+
+        assert!(!effects.take_frozen_frame());
+    }
 }
@@ -0,0 +1,616 @@
+//! The boy's companion. The dog trails the boy, copies his jumps once it
+//! reaches the spot he jumped from, and barks at obstacles coming up ahead.
+
+use crate::engine::camera::Camera;
+use crate::engine::{Audio, Cell, Point, Rect, Renderer, Sheet, Sound};
+use crate::game::{Actor, Obstacle, RedHatBoy, HEIGHT, WIDTH};
+use dog_states::*;
+use std::collections::VecDeque;
+use web_sys::HtmlImageElement;
+
+const DOG_WIDTH: i16 = 80;
+const DOG_HEIGHT: i16 = 56;
+const FLOOR: i16 = HEIGHT - DOG_HEIGHT;
+const BARK_DISTANCE: i16 = 250;
+const BARK_COOLDOWN_FRAMES: u16 = 90;
+
+pub struct Dog {
+    state_machine: DogStateMachine,
+    follower: Follower,
+    sprite_sheet: Sheet,
+    image: HtmlImageElement,
+    audio: Audio,
+    bark_sound: Sound,
+}
+
+impl Dog {
+    pub fn new(sheet: Sheet, image: HtmlImageElement, audio: Audio, bark_sound: Sound) -> Self {
+        Dog {
+            state_machine: DogStateMachine::Idle(DogState::new()),
+            follower: Follower::default(),
+            sprite_sheet: sheet,
+            image,
+            audio,
+            bark_sound,
+        }
+    }
+
+    pub fn reset(dog: Self) -> Self {
+        Dog::new(dog.sprite_sheet, dog.image, dog.audio, dog.bark_sound)
+    }
+
+    pub fn run(&mut self) {
+        self.state_machine = self.state_machine.clone().transition(DogEvent::Run)
+    }
+
+    /// Keeps up with the boy: sits down when he stops, gets up again when he
+    /// runs on, and jumps once it has run as far as the boy had when he
+    /// jumped.
+    pub fn follow(&mut self, boy: &RedHatBoy, walking_speed: i16) {
+        self.state_machine = self.state_machine.clone().keep_pace(walking_speed);
+        if walking_speed == 0 {
+            return;
+        }
+        self.move_horizontally(walking_speed);
+        let gap = boy.bounding_box().right() - self.bounding_box().right();
+        if self.follower.follow(boy.jumping(), gap, walking_speed) {
+            self.state_machine = self.state_machine.clone().transition(DogEvent::Jump);
+        }
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.state_machine = self.state_machine.clone().transition(DogEvent::Move(x))
+    }
+
+    pub fn watch(&mut self, obstacles: &[Box<dyn Obstacle>], camera: &Camera) {
+        let nose = self.bounding_box().right();
+        let nearest = obstacles
+            .iter()
+            .filter(|obstacle| obstacle.is_hazard())
+            .map(|obstacle| obstacle.left() - nose)
+            .filter(|distance| *distance > 0)
+            .min();
+        if self.follower.should_bark(nearest) && self.state_machine.can_bark() {
+            self.state_machine = self.state_machine.clone().transition(DogEvent::Bark);
+            self.play_bark_sound(camera);
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.state_machine = self.state_machine.clone().update()
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        let Some(sprite) = self.current_sprite() else {
+            error!("Dog sprite {} not found", self.state_machine.sprite_name());
+            return;
+        };
+
+        renderer
+            .draw_image(
+                &self.image,
+                &Rect::new_from_x_y(
+                    sprite.frame.x,
+                    sprite.frame.y,
+                    sprite.frame.w,
+                    sprite.frame.h,
+                ),
+                &self.destination_box(sprite),
+            )
+            .expect("failed to draw dog");
+    }
+
+    fn current_sprite(&self) -> Option<&Cell> {
+        self.sprite_sheet
+            .frames
+            .get(&self.state_machine.sprite_name())
+    }
+
+    fn destination_box(&self, sprite: &Cell) -> Rect {
+        let position = self.state_machine.context().position;
+        Rect::new_from_x_y(
+            position.x + sprite.sprite_source_size.x,
+            position.y + sprite.sprite_source_size.y,
+            sprite.frame.w,
+            sprite.frame.h,
+        )
+    }
+
+    fn bounding_box(&self) -> Rect {
+        let position = self.state_machine.context().position;
+        Rect::new_from_x_y(position.x, position.y, DOG_WIDTH, DOG_HEIGHT)
+    }
+
+    fn play_bark_sound(&self, camera: &Camera) {
+        let x = camera
+            .on_screen(Point {
+                x: self.bounding_box().right(),
+                y: 0,
+            })
+            .x;
+        if let Err(err) = self.audio.play_sound_at(&self.bark_sound, x, WIDTH) {
+            log!("Error playing bark sound {:#?}", err);
+        }
+    }
+}
+
+/// The dog's decisions, kept apart from drawing and sound so they can be
+/// tested without a browser.
+#[derive(Default)]
+struct Follower {
+    leader_was_jumping: bool,
+    // Frames left before each jump the boy made is copied, oldest first.
+    pending_jumps: VecDeque<u16>,
+    bark_cooldown: u16,
+}
+
+impl Follower {
+    /// Called once a frame with where the boy is. Returns true on the frame
+    /// the dog should jump.
+    fn follow(&mut self, leader_jumping: bool, gap: i16, walking_speed: i16) -> bool {
+        if leader_jumping && !self.leader_was_jumping {
+            let delay = gap.max(0) / walking_speed.max(1);
+            self.pending_jumps.push_back(delay as u16);
+        }
+        self.leader_was_jumping = leader_jumping;
+
+        self.pending_jumps
+            .iter_mut()
+            .for_each(|frames| *frames = frames.saturating_sub(1));
+        if self.pending_jumps.front() == Some(&0) {
+            self.pending_jumps.pop_front();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Barks at the first obstacle that comes within range, then stays
+    /// quiet for a while so one obstacle gets one bark.
+    fn should_bark(&mut self, nearest_obstacle: Option<i16>) -> bool {
+        self.bark_cooldown = self.bark_cooldown.saturating_sub(1);
+        match nearest_obstacle {
+            Some(distance) if distance <= BARK_DISTANCE && self.bark_cooldown == 0 => {
+                self.bark_cooldown = BARK_COOLDOWN_FRAMES;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum DogStateMachine {
+    Idle(DogState<Idle>),
+    Running(DogState<Running>),
+    Jumping(DogState<Jumping>),
+    Barking(DogState<Barking>),
+}
+
+enum DogEvent {
+    Run,
+    Jump,
+    Bark,
+    Sit,
+    Move(i16),
+    Update,
+}
+
+impl DogStateMachine {
+    fn transition(self, event: DogEvent) -> Self {
+        match (self.clone(), event) {
+            (DogStateMachine::Idle(state), DogEvent::Run) => state.run().into(),
+            (DogStateMachine::Idle(_), DogEvent::Jump) => self,
+            (DogStateMachine::Idle(_), DogEvent::Bark) => self,
+            (DogStateMachine::Idle(_), DogEvent::Sit) => self,
+            (DogStateMachine::Running(_), DogEvent::Run) => self,
+            (DogStateMachine::Running(state), DogEvent::Jump) => state.jump().into(),
+            (DogStateMachine::Running(state), DogEvent::Bark) => state.bark().into(),
+            (DogStateMachine::Running(state), DogEvent::Sit) => state.sit().into(),
+            (DogStateMachine::Jumping(_), DogEvent::Run) => self,
+            (DogStateMachine::Jumping(_), DogEvent::Jump) => self,
+            (DogStateMachine::Jumping(_), DogEvent::Bark) => self,
+            (DogStateMachine::Jumping(_), DogEvent::Sit) => self,
+            (DogStateMachine::Barking(_), DogEvent::Run) => self,
+            (DogStateMachine::Barking(state), DogEvent::Jump) => state.jump().into(),
+            (DogStateMachine::Barking(_), DogEvent::Bark) => self,
+            (DogStateMachine::Barking(state), DogEvent::Sit) => state.sit().into(),
+            (DogStateMachine::Idle(state), DogEvent::Move(x)) => state.move_horizontally(x).into(),
+            (DogStateMachine::Running(state), DogEvent::Move(x)) => {
+                state.move_horizontally(x).into()
+            }
+            (DogStateMachine::Jumping(state), DogEvent::Move(x)) => {
+                state.move_horizontally(x).into()
+            }
+            (DogStateMachine::Barking(state), DogEvent::Move(x)) => {
+                state.move_horizontally(x).into()
+            }
+            (DogStateMachine::Idle(state), DogEvent::Update) => state.update().into(),
+            (DogStateMachine::Running(state), DogEvent::Update) => state.update().into(),
+            (DogStateMachine::Jumping(state), DogEvent::Update) => state.update().into(),
+            (DogStateMachine::Barking(state), DogEvent::Update) => state.update().into(),
+        }
+    }
+
+    fn update(self) -> Self {
+        self.transition(DogEvent::Update)
+    }
+
+    fn keep_pace(self, walking_speed: i16) -> Self {
+        if walking_speed == 0 {
+            self.transition(DogEvent::Sit)
+        } else {
+            self.transition(DogEvent::Run)
+        }
+    }
+
+    fn can_bark(&self) -> bool {
+        matches!(self, DogStateMachine::Running(_))
+    }
+
+    fn frame_name(&self) -> &str {
+        match self {
+            DogStateMachine::Idle(state) => state.frame_name(),
+            DogStateMachine::Running(state) => state.frame_name(),
+            DogStateMachine::Jumping(state) => state.frame_name(),
+            DogStateMachine::Barking(state) => state.frame_name(),
+        }
+    }
+
+    fn context(&self) -> &DogContext {
+        match self {
+            DogStateMachine::Idle(state) => state.context(),
+            DogStateMachine::Running(state) => state.context(),
+            DogStateMachine::Jumping(state) => state.context(),
+            DogStateMachine::Barking(state) => state.context(),
+        }
+    }
+
+    fn sprite_name(&self) -> String {
+        format!(
+            "{} ({}).png",
+            self.frame_name(),
+            (self.context().frame / 3) + 1,
+        )
+    }
+}
+
+impl From<DogState<Idle>> for DogStateMachine {
+    fn from(state: DogState<Idle>) -> Self {
+        DogStateMachine::Idle(state)
+    }
+}
+
+impl From<DogState<Running>> for DogStateMachine {
+    fn from(state: DogState<Running>) -> Self {
+        DogStateMachine::Running(state)
+    }
+}
+
+impl From<DogState<Jumping>> for DogStateMachine {
+    fn from(state: DogState<Jumping>) -> Self {
+        DogStateMachine::Jumping(state)
+    }
+}
+
+impl From<DogState<Barking>> for DogStateMachine {
+    fn from(state: DogState<Barking>) -> Self {
+        DogStateMachine::Barking(state)
+    }
+}
+
+impl From<JumpingEndState> for DogStateMachine {
+    fn from(state: JumpingEndState) -> Self {
+        match state {
+            JumpingEndState::Complete(running) => running.into(),
+            JumpingEndState::Jumping(jumping) => jumping.into(),
+        }
+    }
+}
+
+impl From<BarkingEndState> for DogStateMachine {
+    fn from(state: BarkingEndState) -> Self {
+        match state {
+            BarkingEndState::Complete(running) => running.into(),
+            BarkingEndState::Barking(barking) => barking.into(),
+        }
+    }
+}
+
+mod dog_states {
+    use super::FLOOR;
+    use crate::engine::Point;
+
+    const STARTING_POINT: i16 = 0;
+    const IDLE_FRAME_NAME: &str = "Idle";
+    const RUN_FRAME_NAME: &str = "Run";
+    const JUMPING_FRAME_NAME: &str = "Jump";
+    const BARKING_FRAME_NAME: &str = "Bark";
+    const IDLE_FRAMES: u8 = 11;
+    const RUNNING_FRAMES: u8 = 23;
+    const JUMPING_FRAMES: u8 = 11;
+    const BARKING_FRAMES: u8 = 11;
+    const JUMP_SPEED: i16 = -25;
+    const GRAVITY: i16 = 1;
+    const TERMINAL_VELOCITY: i16 = 20;
+
+    #[derive(Clone)]
+    pub struct DogState<S> {
+        context: DogContext,
+        _state: S,
+    }
+
+    #[derive(Clone)]
+    pub struct DogContext {
+        pub frame: u8,
+        pub position: Point,
+        pub velocity: Point,
+    }
+
+    impl DogContext {
+        fn update(mut self, frame_count: u8) -> Self {
+            if self.velocity.y < TERMINAL_VELOCITY {
+                self.velocity.y += GRAVITY;
+            }
+            if self.frame < frame_count {
+                self.frame += 1;
+            } else {
+                self.frame = 0;
+            }
+            self.position.y += self.velocity.y;
+            if self.position.y > FLOOR {
+                self.position.y = FLOOR
+            }
+
+            self
+        }
+
+        fn reset_frame(mut self) -> Self {
+            self.frame = 0;
+            self
+        }
+
+        fn set_vertical_velocity(mut self, y: i16) -> Self {
+            self.velocity.y = y;
+            self
+        }
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct Idle;
+    #[derive(Copy, Clone)]
+    pub struct Running;
+    #[derive(Copy, Clone)]
+    pub struct Jumping;
+    #[derive(Copy, Clone)]
+    pub struct Barking;
+
+    impl<S> DogState<S> {
+        pub fn context(&self) -> &DogContext {
+            &self.context
+        }
+
+        pub fn move_horizontally(mut self, x: i16) -> Self {
+            self.context.position.x += x;
+            self
+        }
+    }
+
+    impl DogState<Idle> {
+        pub fn new() -> Self {
+            DogState {
+                context: DogContext {
+                    frame: 0,
+                    position: Point {
+                        x: STARTING_POINT,
+                        y: FLOOR,
+                    },
+                    velocity: Point { x: 0, y: 0 },
+                },
+                _state: Idle,
+            }
+        }
+
+        pub fn update(mut self) -> Self {
+            self.context = self.context.update(IDLE_FRAMES);
+            self
+        }
+
+        pub fn frame_name(&self) -> &str {
+            IDLE_FRAME_NAME
+        }
+
+        pub fn run(self) -> DogState<Running> {
+            DogState {
+                context: self.context.reset_frame(),
+                _state: Running,
+            }
+        }
+    }
+
+    impl DogState<Running> {
+        pub fn update(mut self) -> Self {
+            self.context = self.context.update(RUNNING_FRAMES);
+            self
+        }
+
+        pub fn frame_name(&self) -> &str {
+            RUN_FRAME_NAME
+        }
+
+        pub fn jump(self) -> DogState<Jumping> {
+            DogState {
+                context: self.context.set_vertical_velocity(JUMP_SPEED).reset_frame(),
+                _state: Jumping,
+            }
+        }
+
+        pub fn bark(self) -> DogState<Barking> {
+            DogState {
+                context: self.context.reset_frame(),
+                _state: Barking,
+            }
+        }
+
+        pub fn sit(self) -> DogState<Idle> {
+            DogState {
+                context: self.context.reset_frame(),
+                _state: Idle,
+            }
+        }
+    }
+
+    impl DogState<Jumping> {
+        pub fn update(mut self) -> JumpingEndState {
+            self.context = self.context.update(JUMPING_FRAMES);
+
+            if self.context.position.y >= FLOOR {
+                JumpingEndState::Complete(self.land())
+            } else {
+                JumpingEndState::Jumping(self)
+            }
+        }
+
+        pub fn frame_name(&self) -> &str {
+            JUMPING_FRAME_NAME
+        }
+
+        fn land(self) -> DogState<Running> {
+            DogState {
+                context: self.context.reset_frame(),
+                _state: Running,
+            }
+        }
+    }
+
+    pub enum JumpingEndState {
+        Complete(DogState<Running>),
+        Jumping(DogState<Jumping>),
+    }
+
+    impl DogState<Barking> {
+        pub fn update(mut self) -> BarkingEndState {
+            self.context = self.context.update(BARKING_FRAMES);
+
+            if self.context.frame >= BARKING_FRAMES {
+                BarkingEndState::Complete(self.stop_barking())
+            } else {
+                BarkingEndState::Barking(self)
+            }
+        }
+
+        pub fn frame_name(&self) -> &str {
+            BARKING_FRAME_NAME
+        }
+
+        pub fn jump(self) -> DogState<Jumping> {
+            DogState {
+                context: self.context.set_vertical_velocity(JUMP_SPEED).reset_frame(),
+                _state: Jumping,
+            }
+        }
+
+        pub fn sit(self) -> DogState<Idle> {
+            DogState {
+                context: self.context.reset_frame(),
+                _state: Idle,
+            }
+        }
+
+        fn stop_barking(self) -> DogState<Running> {
+            DogState {
+                context: self.context.reset_frame(),
+                _state: Running,
+            }
+        }
+    }
+
+    pub enum BarkingEndState {
+        Complete(DogState<Running>),
+        Barking(DogState<Barking>),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running_dog() -> DogStateMachine {
+        DogStateMachine::Idle(DogState::new()).transition(DogEvent::Run)
+    }
+
+    #[test]
+    fn the_dog_copies_a_jump_once_it_covers_the_gap() {
+        let mut follower = Follower::default();
+
+        assert!(!follower.follow(true, 40, 4));
+        let frames_until_jump = (1..20)
+            .position(|_| follower.follow(true, 40, 4))
+            .map(|frames| frames + 2);
+
+        assert_eq!(frames_until_jump, Some(10));
+    }
+
+    #[test]
+    fn each_jump_is_copied_once() {
+        let mut follower = Follower::default();
+
+        let jumps = (0..100)
+            .filter(|frame| follower.follow(*frame < 30, 40, 4))
+            .count();
+
+        assert_eq!(jumps, 1);
+    }
+
+    #[test]
+    fn the_dog_waits_out_a_cooldown_between_barks() {
+        let mut follower = Follower::default();
+
+        assert!(!follower.should_bark(Some(BARK_DISTANCE + 4)));
+        assert!(follower.should_bark(Some(BARK_DISTANCE)));
+        assert!(!follower.should_bark(Some(BARK_DISTANCE - 4)));
+        assert!(!follower.should_bark(None));
+    }
+
+    #[test]
+    fn barking_finishes_back_at_a_run() {
+        let mut dog = running_dog().transition(DogEvent::Bark);
+        assert!(matches!(dog, DogStateMachine::Barking(_)));
+
+        (0..50).for_each(|_| dog = dog.clone().update());
+
+        assert!(matches!(dog, DogStateMachine::Running(_)));
+    }
+
+    #[test]
+    fn a_jumping_dog_lands_back_on_the_floor() {
+        let mut dog = running_dog().transition(DogEvent::Jump);
+        assert!(matches!(dog, DogStateMachine::Jumping(_)));
+
+        (0..100).for_each(|_| dog = dog.clone().update());
+
+        assert!(matches!(dog, DogStateMachine::Running(_)));
+        assert_eq!(dog.context().position.y, FLOOR);
+    }
+
+    #[test]
+    fn the_dog_only_sits_when_it_is_on_the_ground() {
+        let jumping = running_dog()
+            .transition(DogEvent::Jump)
+            .transition(DogEvent::Sit);
+        let running = running_dog().transition(DogEvent::Sit);
+
+        assert!(matches!(jumping, DogStateMachine::Jumping(_)));
+        assert!(matches!(running, DogStateMachine::Idle(_)));
+    }
+
+    #[test]
+    fn a_sitting_dog_runs_again_once_the_boy_does() {
+        let sitting = running_dog().keep_pace(0);
+        assert!(matches!(sitting, DogStateMachine::Idle(_)));
+
+        let running = sitting.keep_pace(4);
+
+        assert!(matches!(running, DogStateMachine::Running(_)));
+    }
+}
@@ -0,0 +1,600 @@
+//! Replays segments against the real `red_hat_boy_states` physics without a
+//! browser, searching over jump and slide timings to find out whether a
+//! segment can be cleared and how much timing slack the player gets.
+
+use crate::difficulty::Difficulty;
+use crate::engine::{Point, Rect, Sheet};
+use crate::game::red_hat_boy_states::RedHatBoyState;
+use crate::game::{
+    barrier_collision, new_camera, pit_collision, Actor, Collision, EnemyBody, Event, PlatformBody,
+    RedHatBoyStateMachine, GROUND_DEPTH, HEIGHT,
+};
+use crate::movement::Movement;
+use crate::segment::{ObstacleDefinition, SegmentDefinition, SegmentLibrary};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::{discriminant, Discriminant};
+
+// Extra frames to simulate after the last obstacle should have been passed,
+// so a boy still in the air has time to come down.
+const SETTLE_FRAMES: usize = 120;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Clearance {
+    /// No input of any kind gets the boy through.
+    Unclearable,
+    /// The widest timing window, in frames, that still clears the segment.
+    Window(u32),
+    /// The boy gets through without pressing anything.
+    NoInputNeeded,
+}
+
+pub struct SegmentReport {
+    pub segment: String,
+    pub running_speed: i16,
+    pub clearance: Clearance,
+}
+
+impl SegmentReport {
+    pub fn is_ok(&self, min_window: u32) -> bool {
+        match self.clearance {
+            Clearance::Unclearable => false,
+            Clearance::Window(frames) => frames >= min_window,
+            Clearance::NoInputNeeded => true,
+        }
+    }
+}
+
+impl fmt::Display for SegmentReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at speed {}: ", self.segment, self.running_speed)?;
+        match self.clearance {
+            Clearance::Unclearable => write!(f, "cannot be cleared"),
+            Clearance::Window(1) => write!(f, "needs frame-perfect input"),
+            Clearance::Window(frames) => write!(f, "clearable with a {} frame window", frames),
+            Clearance::NoInputNeeded => write!(f, "clearable without input"),
+        }
+    }
+}
+
+/// Checks every segment at each running speed it can appear at. Segments
+/// that no difficulty stage uses are checked at the starting speed.
+pub fn validate_segments(
+    segments_json: &str,
+    difficulty_json: &str,
+    rhb_json: &str,
+    enemies_json: &str,
+    image_size: impl Fn(&str) -> Result<(i16, i16)>,
+) -> Result<Vec<SegmentReport>> {
+    let segments = serde_json::from_str::<SegmentLibrary>(segments_json)?.validate()?;
+    let difficulty = serde_json::from_str::<Difficulty>(difficulty_json)?.validate()?;
+    let sheet: Sheet = serde_json::from_str(rhb_json)?;
+    let enemies: Sheet = serde_json::from_str(enemies_json)?;
+
+    segments
+        .segments()
+        .iter()
+        .flat_map(|segment| {
+            let mut speeds: Vec<i16> = difficulty
+                .stages()
+                .iter()
+                .filter(|stage| stage.segments.contains(&segment.name))
+                .map(|stage| stage.running_speed)
+                .collect();
+            speeds.sort();
+            speeds.dedup();
+            if speeds.is_empty() {
+                speeds.push(difficulty.stage_at(0).running_speed);
+            }
+            speeds.into_iter().map(move |speed| (segment, speed))
+        })
+        .map(|(segment, running_speed)| {
+            let course = Course::new(segment, &enemies, &image_size)?;
+            let clearance = Simulation::new(&sheet, &course, running_speed).clearance();
+            Ok(SegmentReport {
+                segment: segment.name.clone(),
+                running_speed,
+                clearance,
+            })
+        })
+        .collect()
+}
+
+/// Reads the width and height out of a PNG's IHDR chunk.
+pub fn png_size(bytes: &[u8]) -> Result<(i16, i16)> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if bytes.len() < 24 || !bytes.starts_with(SIGNATURE) || &bytes[12..16] != b"IHDR" {
+        return Err(anyhow!("Not a PNG image"));
+    }
+    let width = u32::from_be_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+    let height = u32::from_be_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]);
+
+    Ok((
+        i16::try_from(width).map_err(|_| anyhow!("PNG is too wide"))?,
+        i16::try_from(height).map_err(|_| anyhow!("PNG is too tall"))?,
+    ))
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Collider {
+    Barrier(Rect),
+    Pit(Rect),
+    Platform(PlatformBody),
+    Enemy(EnemyBody),
+}
+
+impl Collider {
+    fn update(&mut self, view: &Rect) {
+        match self {
+            Collider::Barrier(_) | Collider::Pit(_) => {}
+            Collider::Platform(platform) => platform.update(),
+            Collider::Enemy(enemy) => enemy.update(view),
+        }
+    }
+
+    fn check_intersection(&self, actor: &dyn Actor) -> Option<Collision> {
+        match self {
+            Collider::Barrier(bounding_box) => barrier_collision(actor, bounding_box),
+            Collider::Pit(gap) => pit_collision(actor, gap),
+            Collider::Platform(platform) => platform.check_intersection(actor),
+            Collider::Enemy(enemy) => enemy.check_intersection(actor),
+        }
+    }
+
+    fn landed_on(&mut self) {
+        match self {
+            Collider::Barrier(_) | Collider::Pit(_) => {}
+            Collider::Platform(platform) => platform.landed_on(),
+            Collider::Enemy(enemy) => enemy.landed_on(),
+        }
+    }
+
+    fn right(&self) -> i16 {
+        match self {
+            Collider::Barrier(bounding_box) | Collider::Pit(bounding_box) => bounding_box.right(),
+            Collider::Platform(platform) => platform.right(),
+            Collider::Enemy(enemy) => enemy.bounding_box.right(),
+        }
+    }
+}
+
+/// A segment's obstacles without their sprites, positioned relative to the
+/// segment's start.
+struct Course {
+    colliders: Vec<Collider>,
+    right: i16,
+}
+
+impl Course {
+    fn new(
+        segment: &SegmentDefinition,
+        enemies: &Sheet,
+        image_size: &impl Fn(&str) -> Result<(i16, i16)>,
+    ) -> Result<Self> {
+        // Pickups don't get in the boy's way, so they have no collider.
+        let colliders = segment
+            .obstacles
+            .iter()
+            .filter_map(|obstacle| match obstacle {
+                ObstacleDefinition::Barrier { image, x, y } => Some(
+                    image_size(image)
+                        .map(|(w, h)| Collider::Barrier(Rect::new_from_x_y(*x, *y, w, h))),
+                ),
+                ObstacleDefinition::Platform {
+                    bounding_boxes,
+                    x,
+                    y,
+                    behavior,
+                    ..
+                } => {
+                    let bounding_boxes: Vec<Rect> = bounding_boxes
+                        .iter()
+                        .map(|bounding_box| {
+                            Rect::new_from_x_y(
+                                bounding_box.x,
+                                bounding_box.y,
+                                bounding_box.w,
+                                bounding_box.h,
+                            )
+                        })
+                        .collect();
+                    Some(Ok(Collider::Platform(PlatformBody::new(
+                        Point { x: *x, y: *y },
+                        &bounding_boxes,
+                        *behavior,
+                    ))))
+                }
+                ObstacleDefinition::Pit { x, width } => Some(Ok(Collider::Pit(
+                    Rect::new_from_x_y(*x, HEIGHT, *width, GROUND_DEPTH),
+                ))),
+                ObstacleDefinition::Enemy { behavior, x, y } => {
+                    let size = |name: &str| {
+                        enemies
+                            .frames
+                            .get(name)
+                            .map(|cell| (cell.frame.w, cell.frame.h))
+                            .ok_or_else(|| {
+                                anyhow!("Enemy sprite {} is not in the enemy sheet", name)
+                            })
+                    };
+                    Some(size(behavior.sprite_name()).and_then(|(w, h)| {
+                        let projectile_size =
+                            behavior.projectile_sprite_name().map(size).transpose()?;
+                        Ok(Collider::Enemy(EnemyBody::new(
+                            *behavior,
+                            Rect::new_from_x_y(*x, *y, w, h),
+                            projectile_size,
+                        )))
+                    }))
+                }
+                ObstacleDefinition::CollectibleRow { .. }
+                | ObstacleDefinition::CollectibleArc { .. } => None,
+            })
+            .collect::<Result<Vec<Collider>>>()?;
+        // Enemies only ever move left of where they start, and their
+        // projectiles further left still.
+        let right = colliders.iter().map(Collider::right).max().unwrap_or(0);
+
+        Ok(Course { colliders, right })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Input {
+    Jump,
+    Slide,
+}
+
+/// The boy along with the obstacles, which move and change as he plays
+/// through the segment.
+#[derive(Clone)]
+struct Snapshot {
+    boy: RedHatBoyStateMachine,
+    colliders: Vec<Collider>,
+}
+
+enum Step {
+    Alive(Snapshot),
+    Cleared,
+    KnockedOut,
+}
+
+type StateKey = (
+    usize,
+    Discriminant<RedHatBoyStateMachine>,
+    u8,
+    i16,
+    i16,
+    Vec<Collider>,
+);
+
+struct Simulation<'a> {
+    sheet: &'a Sheet,
+    course: &'a Course,
+    running_speed: i16,
+    // Where the segment starts on screen in the first frame.
+    start_x: i16,
+    frame_limit: usize,
+    tolerances: HashMap<StateKey, u32>,
+}
+
+impl<'a> Simulation<'a> {
+    fn new(sheet: &'a Sheet, course: &'a Course, running_speed: i16) -> Self {
+        let boy = Self::running_boy();
+        // The boy reaches the segment running along the floor, with a short
+        // run-up so the first obstacle isn't already on top of him.
+        let start_x = boy.bounding_box(sheet).right() + running_speed * 10;
+        let frames_to_pass = (start_x + course.right) / running_speed.max(1);
+
+        Simulation {
+            sheet,
+            course,
+            running_speed,
+            start_x,
+            frame_limit: frames_to_pass.max(0) as usize + SETTLE_FRAMES,
+            tolerances: HashMap::new(),
+        }
+    }
+
+    /// One life and no jump assists, so segments stay clearable without them.
+    fn running_boy() -> RedHatBoyStateMachine {
+        RedHatBoyStateMachine::Idle(RedHatBoyState::new(1, Movement::default()))
+            .transition(Event::Run)
+    }
+
+    fn clearance(&mut self) -> Clearance {
+        let start = Snapshot {
+            boy: Self::running_boy(),
+            colliders: self.course.colliders.clone(),
+        };
+        match self.tolerance(start, 0) {
+            0 => Clearance::Unclearable,
+            u32::MAX => Clearance::NoInputNeeded,
+            frames => Clearance::Window(frames),
+        }
+    }
+
+    fn key(snapshot: &Snapshot, frame: usize) -> StateKey {
+        let context = snapshot.boy.context();
+        (
+            frame,
+            discriminant(&snapshot.boy),
+            context.frame,
+            context.position.y,
+            context.velocity.y,
+            snapshot.colliders.clone(),
+        )
+    }
+
+    /// How many frames of slack the player has from this state: the largest
+    /// `k` such that some input can be pressed on any of `k` consecutive
+    /// frames and every one of those choices still has `k` frames of slack
+    /// afterwards. Zero means the state is lost, `u32::MAX` means no more
+    /// input is needed.
+    fn tolerance(&mut self, snapshot: Snapshot, frame: usize) -> u32 {
+        let key = Self::key(&snapshot, frame);
+        if let Some(tolerance) = self.tolerances.get(&key) {
+            return *tolerance;
+        }
+
+        let mut path = vec![];
+        let mut current = snapshot;
+        let mut current_frame = frame;
+        let idle_outcome = loop {
+            path.push((current.clone(), current_frame));
+            match self.step(current, current_frame, None) {
+                Step::Alive(next) => {
+                    current = next;
+                    current_frame += 1;
+                }
+                outcome => break outcome,
+            }
+        };
+
+        let tolerance = if matches!(idle_outcome, Step::Cleared) {
+            u32::MAX
+        } else {
+            [Input::Jump, Input::Slide]
+                .iter()
+                .map(|input| {
+                    let slack: Vec<u32> = path
+                        .iter()
+                        .map(|(state, state_frame)| {
+                            self.tolerance_after(state.clone(), *state_frame, *input)
+                        })
+                        .collect();
+                    widest_window(&slack)
+                })
+                .max()
+                .unwrap_or(0)
+        };
+
+        self.tolerances.insert(key, tolerance);
+        tolerance
+    }
+
+    fn tolerance_after(&mut self, snapshot: Snapshot, frame: usize, input: Input) -> u32 {
+        if !matches!(snapshot.boy, RedHatBoyStateMachine::Running(_)) {
+            return 0;
+        }
+        match self.step(snapshot, frame, Some(input)) {
+            Step::Alive(next) => self.tolerance(next, frame + 1),
+            Step::Cleared => u32::MAX,
+            Step::KnockedOut => 0,
+        }
+    }
+
+    /// Mirrors one tick of `WalkTheDogState<Walking>::update`: input, then
+    /// the boy's own update, then running forward, the obstacles' updates
+    /// and collisions. Here the boy stays put and the course moves past him,
+    /// which comes to the same thing.
+    fn step(&self, snapshot: Snapshot, frame: usize, input: Option<Input>) -> Step {
+        let Snapshot { boy, mut colliders } = snapshot;
+        let boy = match input {
+            Some(Input::Jump) => boy.transition(Event::Jump),
+            Some(Input::Slide) => boy.transition(Event::Slide),
+            None => boy,
+        };
+        let mut boy = boy.update();
+
+        let offset_x = self.start_x - self.running_speed * (frame as i16 + 1);
+        let mut camera = new_camera();
+        camera.follow(boy.context().position);
+        let view = shifted(&camera.view(), -offset_x);
+        colliders
+            .iter_mut()
+            .for_each(|collider| collider.update(&view));
+        for collider in &mut colliders {
+            boy = self.collide(boy, collider, offset_x);
+        }
+
+        if matches!(
+            boy,
+            RedHatBoyStateMachine::Falling(_) | RedHatBoyStateMachine::KnockedOut(_)
+        ) {
+            Step::KnockedOut
+        } else if offset_x + self.course.right < boy.bounding_box(self.sheet).x()
+            && matches!(boy, RedHatBoyStateMachine::Running(_))
+        {
+            Step::Cleared
+        } else if frame >= self.frame_limit {
+            Step::KnockedOut
+        } else {
+            Step::Alive(Snapshot { boy, colliders })
+        }
+    }
+
+    /// Applies the same rules the obstacles use in the game, with the boy
+    /// moved back to the course's coordinates.
+    fn collide(
+        &self,
+        boy: RedHatBoyStateMachine,
+        collider: &mut Collider,
+        offset_x: i16,
+    ) -> RedHatBoyStateMachine {
+        let actor = SimulatedBoy {
+            state_machine: &boy,
+            sheet: self.sheet,
+            offset_x,
+        };
+        let collision = collider.check_intersection(&actor);
+        if matches!(collision, Some(Collision::Land(_) | Collision::Stomp)) {
+            collider.landed_on();
+        }
+        match collision {
+            Some(Collision::Land(position)) => boy.transition(Event::Land(position)),
+            Some(Collision::Stomp) => boy.transition(Event::Bounce),
+            Some(Collision::KnockOut) => boy.transition(Event::KnockOut),
+            Some(Collision::OverPit) => boy.transition(Event::OverPit),
+            None => boy,
+        }
+    }
+}
+
+struct SimulatedBoy<'a> {
+    state_machine: &'a RedHatBoyStateMachine,
+    sheet: &'a Sheet,
+    offset_x: i16,
+}
+
+impl Actor for SimulatedBoy<'_> {
+    fn bounding_box(&self) -> Rect {
+        shifted(&self.state_machine.bounding_box(self.sheet), -self.offset_x)
+    }
+
+    fn pos_y(&self) -> i16 {
+        self.state_machine.context().position.y
+    }
+
+    fn velocity_y(&self) -> i16 {
+        self.state_machine.context().velocity.y
+    }
+}
+
+fn shifted(bounding_box: &Rect, offset_x: i16) -> Rect {
+    Rect::new_from_x_y(
+        bounding_box.x() + offset_x,
+        bounding_box.y(),
+        bounding_box.w,
+        bounding_box.h,
+    )
+}
+
+/// The largest `k` such that `k` consecutive entries are all at least `k`.
+fn widest_window(slack: &[u32]) -> u32 {
+    (1..=slack.len() as u32)
+        .rev()
+        .find(|&k| {
+            slack
+                .windows(k as usize)
+                .any(|window| window.iter().all(|&entry| entry >= k))
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE_PNG: &[u8] = include_bytes!("../../static/Stone.png");
+
+    fn validate(segments_json: &str) -> Vec<SegmentReport> {
+        validate_segments(
+            segments_json,
+            include_str!("../../static/difficulty.json"),
+            include_str!("../../static/rhb.json"),
+            include_str!("../../static/enemies.json"),
+            |_image| png_size(STONE_PNG),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn png_size_reads_the_header() {
+        assert_eq!(png_size(STONE_PNG).unwrap(), (90, 54));
+        assert!(png_size(b"not a png").is_err());
+    }
+
+    #[test]
+    fn widest_window_needs_enough_slack_on_every_frame() {
+        assert_eq!(widest_window(&[]), 0);
+        assert_eq!(widest_window(&[0, 1, 0]), 1);
+        assert_eq!(widest_window(&[0, 5, 5, 5, 0]), 3);
+        assert_eq!(widest_window(&[9, 1, 9, 9]), 2);
+        assert_eq!(widest_window(&[u32::MAX, u32::MAX]), 2);
+    }
+
+    #[test]
+    fn every_bundled_segment_is_clearable() {
+        let reports = validate(include_str!("../../static/segments.json"));
+
+        assert!(!reports.is_empty());
+        reports
+            .iter()
+            .for_each(|report| assert!(report.is_ok(2), "{}", report));
+    }
+
+    #[test]
+    fn a_wall_of_stones_cannot_be_cleared() {
+        let reports = validate(
+            r#"{"segments": [{"name": "wall", "obstacles": [
+                {"type": "barrier", "image": "Stone.png", "x": 100, "y": 546},
+                {"type": "barrier", "image": "Stone.png", "x": 190, "y": 546},
+                {"type": "barrier", "image": "Stone.png", "x": 280, "y": 546},
+                {"type": "barrier", "image": "Stone.png", "x": 370, "y": 546},
+                {"type": "barrier", "image": "Stone.png", "x": 460, "y": 546},
+                {"type": "barrier", "image": "Stone.png", "x": 550, "y": 546}
+            ]}]}"#,
+        );
+
+        assert_eq!(reports[0].clearance, Clearance::Unclearable);
+    }
+
+    #[test]
+    fn a_platform_high_above_the_floor_needs_no_input() {
+        let reports = validate(
+            r#"{"segments": [{"name": "sky", "obstacles": [
+                {"type": "platform", "sprites": ["13.png"],
+                 "bounding_boxes": [{"x": 0, "y": 0, "w": 60, "h": 54}], "x": 300, "y": 100}
+            ]}]}"#,
+        );
+
+        assert_eq!(reports[0].clearance, Clearance::NoInputNeeded);
+    }
+
+    #[test]
+    fn enemies_in_the_way_have_to_be_jumped_or_stomped() {
+        let reports = validate(
+            r#"{"segments": [{"name": "walker", "obstacles": [
+                {"type": "enemy", "behavior": {"type": "walker", "distance": 60, "speed": 1},
+                 "x": 300, "y": 556}
+            ]}]}"#,
+        );
+
+        assert!(matches!(reports[0].clearance, Clearance::Window(_)));
+    }
+
+    fn bridge_over_a_wide_pit(behavior: &str) -> String {
+        format!(
+            r#"{{"segments": [{{"name": "bridge", "obstacles": [
+                {{"type": "platform", "sprites": ["13.png"],
+                 "bounding_boxes": [{{"x": 0, "y": 0, "w": 1000, "h": 54}}],
+                 "x": 100, "y": 420, "behavior": {}}},
+                {{"type": "pit", "x": 150, "width": 950}}
+            ]}}]}}"#,
+            behavior
+        )
+    }
+
+    #[test]
+    fn crumbling_platforms_give_way_under_the_boy() {
+        let solid = validate(&bridge_over_a_wide_pit(r#"{"type": "static"}"#));
+        let crumbling = validate(&bridge_over_a_wide_pit(
+            r#"{"type": "crumbling", "delay": 10}"#,
+        ));
+
+        assert_ne!(solid[0].clearance, Clearance::Unclearable);
+        assert_eq!(crumbling[0].clearance, Clearance::Unclearable);
+    }
+}
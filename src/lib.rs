@@ -1,10 +1,15 @@
 #[macro_use]
 mod browser;
+mod difficulty;
 mod engine;
 mod game;
+mod movement;
+mod score;
 mod segment;
 mod sound;
 
+pub use crate::game::simulation::{png_size, validate_segments, Clearance, SegmentReport};
+
 use crate::engine::GameLoop;
 use crate::game::WalkTheDog;
 use wasm_bindgen::prelude::*;
@@ -0,0 +1,44 @@
+use serde::Deserialize;
+
+/// How forgiving the boy's jump is. Anything left out is off, which gives
+/// the original game where a jump only counts while running on the ground.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Movement {
+    /// How many frames after running off a platform the boy can still jump.
+    pub coyote_frames: u8,
+    /// How many frames a jump pressed in the air is remembered, so it
+    /// happens as soon as the boy lands.
+    pub jump_buffer_frames: u8,
+    /// Whether the boy can jump once more before he lands.
+    pub double_jump: bool,
+}
+
+impl Movement {
+    pub fn air_jumps(&self) -> u8 {
+        u8::from(self.double_jump)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn everything_is_off_by_default() {
+        let movement: Movement = serde_json::from_str("{}").unwrap();
+
+        assert_eq!(movement, Movement::default());
+        assert_eq!(movement.air_jumps(), 0);
+    }
+
+    #[test]
+    fn bundled_movement_is_valid() {
+        let movement: Movement =
+            serde_json::from_str(include_str!("../static/movement.json")).unwrap();
+
+        assert!(movement.coyote_frames > 0);
+        assert!(movement.jump_buffer_frames > 0);
+        assert_eq!(movement.air_jumps(), 1);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::browser::Storage;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+const PIXELS_PER_METER: u32 = 50;
+const POINTS_PER_OBSTACLE: u32 = 10;
+const HIGH_SCORES_KEY: &str = "walk_the_dog.high_scores";
+const MAX_HIGH_SCORES: usize = 10;
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Score {
+    distance: u32,
+    obstacles_cleared: u32,
+    bonus: u32,
+}
+
+impl Score {
+    pub fn new() -> Self {
+        Score::default()
+    }
+
+    /// Records one frame of the boy moving right at `velocity`.
+    pub fn travel(&mut self, velocity: i16) {
+        self.distance += u32::try_from(velocity).unwrap_or(0);
+    }
+
+    pub fn clear_obstacles(&mut self, count: usize) {
+        self.obstacles_cleared += count as u32;
+    }
+
+    pub fn collect(&mut self, points: u32) {
+        self.bonus += points;
+    }
+
+    pub fn meters(&self) -> u32 {
+        self.distance / PIXELS_PER_METER
+    }
+
+    pub fn obstacles_cleared(&self) -> u32 {
+        self.obstacles_cleared
+    }
+
+    pub fn points(&self) -> u32 {
+        self.meters() + self.obstacles_cleared * POINTS_PER_OBSTACLE + self.bonus
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub points: u32,
+    pub meters: u32,
+    pub obstacles_cleared: u32,
+}
+
+impl From<&Score> for HighScore {
+    fn from(score: &Score) -> Self {
+        HighScore {
+            points: score.points(),
+            meters: score.meters(),
+            obstacles_cleared: score.obstacles_cleared(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct HighScores {
+    entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    pub fn load(storage: &dyn Storage) -> Result<Self> {
+        match storage.get_item(HIGH_SCORES_KEY)? {
+            Some(json) => serde_json::from_str(&json)
+                .map_err(|err| anyhow!("Could not parse high scores {:#?}", err)),
+            None => Ok(HighScores::default()),
+        }
+    }
+
+    pub fn save(&self, storage: &mut dyn Storage) -> Result<()> {
+        let json = serde_json::to_string(self)
+            .map_err(|err| anyhow!("Could not serialize high scores {:#?}", err))?;
+        storage.set_item(HIGH_SCORES_KEY, &json)
+    }
+
+    /// Adds the score to the table and returns its rank, or `None` when it
+    /// didn't make the top ten. Ties rank below the existing entries.
+    pub fn insert(&mut self, score: &Score) -> Option<usize> {
+        let high_score = HighScore::from(score);
+        let rank = self
+            .entries
+            .iter()
+            .position(|entry| entry.points < high_score.points)
+            .unwrap_or(self.entries.len());
+
+        if rank >= MAX_HIGH_SCORES {
+            return None;
+        }
+        self.entries.insert(rank, high_score);
+        self.entries.truncate(MAX_HIGH_SCORES);
+        Some(rank)
+    }
+
+    pub fn entries(&self) -> &[HighScore] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::MemoryStorage;
+
+    fn score_with_obstacles(obstacles: usize) -> Score {
+        let mut score = Score::new();
+        score.clear_obstacles(obstacles);
+        score
+    }
+
+    #[test]
+    fn travel_accumulates_the_walking_speed() {
+        let mut score = Score::new();
+
+        (0..25).for_each(|_| score.travel(4));
+
+        assert_eq!(score.meters(), 2);
+    }
+
+    #[test]
+    fn standing_still_does_not_add_distance() {
+        let mut score = Score::new();
+
+        (0..25).for_each(|_| score.travel(0));
+
+        assert_eq!(score.meters(), 0);
+    }
+
+    #[test]
+    fn points_combine_meters_and_obstacles() {
+        let mut score = Score::new();
+        (0..50).for_each(|_| score.travel(4));
+        score.clear_obstacles(3);
+
+        assert_eq!(score.meters(), 4);
+        assert_eq!(score.points(), 4 + 3 * POINTS_PER_OBSTACLE);
+    }
+
+    #[test]
+    fn collected_pickups_add_to_the_points() {
+        let mut score = score_with_obstacles(1);
+        score.collect(5);
+        score.collect(25);
+
+        assert_eq!(score.points(), POINTS_PER_OBSTACLE + 30);
+        assert_eq!(score.obstacles_cleared(), 1);
+    }
+
+    #[test]
+    fn loading_from_empty_storage_gives_an_empty_table() {
+        let storage = MemoryStorage::default();
+
+        let high_scores = HighScores::load(&storage).unwrap();
+
+        assert!(high_scores.entries().is_empty());
+    }
+
+    #[test]
+    fn high_scores_are_kept_in_descending_order() {
+        let mut high_scores = HighScores::default();
+
+        high_scores.insert(&score_with_obstacles(1));
+        high_scores.insert(&score_with_obstacles(3));
+        let rank = high_scores.insert(&score_with_obstacles(2));
+
+        let points: Vec<u32> = high_scores.entries().iter().map(|e| e.points).collect();
+        assert_eq!(points, vec![30, 20, 10]);
+        assert_eq!(rank, Some(1));
+    }
+
+    #[test]
+    fn only_the_top_ten_are_kept() {
+        let mut high_scores = HighScores::default();
+        (1..=MAX_HIGH_SCORES).for_each(|obstacles| {
+            high_scores.insert(&score_with_obstacles(obstacles));
+        });
+
+        assert_eq!(high_scores.insert(&score_with_obstacles(0)), None);
+        assert_eq!(high_scores.insert(&score_with_obstacles(20)), Some(0));
+        assert_eq!(high_scores.entries().len(), MAX_HIGH_SCORES);
+        assert_eq!(high_scores.entries().last().unwrap().points, 20);
+    }
+
+    #[test]
+    fn high_scores_survive_a_round_trip_through_storage() {
+        let mut storage = MemoryStorage::default();
+        let mut high_scores = HighScores::default();
+        high_scores.insert(&score_with_obstacles(4));
+
+        high_scores.save(&mut storage).unwrap();
+        let loaded = HighScores::load(&storage).unwrap();
+
+        assert_eq!(loaded.entries(), high_scores.entries());
+    }
+
+    #[test]
+    fn corrupt_storage_is_an_error() {
+        let mut storage = MemoryStorage::default();
+        storage.set_item(HIGH_SCORES_KEY, "not json").unwrap();
+
+        assert!(HighScores::load(&storage).is_err());
+    }
+}
@@ -1,74 +1,685 @@
-use crate::engine::{Image, Point, Rect, SpriteSheet};
-use crate::game::{Barrier, Obstacle, Platform};
+use crate::difficulty::Difficulty;
+use crate::engine::{Image, Point, Rect, SheetRect, SpriteSheet};
+use crate::game::{
+    Barrier, Enemy, EnemyBehavior, Obstacle, Pickup, Pit, Platform, PlatformBehavior, PIT_SPRITE,
+};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use web_sys::HtmlImageElement;
 
-const STONE_ON_GROUND: i16 = 546;
-const FIRST_PLATFORM: i16 = 370;
-const LOW_PLATFORM: i16 = 420;
-const HIGH_PLATFORM: i16 = 375;
-const FLOATING_PLATFORM_SPRITES: &[&str] = &["13.png", "14.png", "15.png"];
-const FLOATING_PLATFORM_BOUNDING_BOXES: &[Rect] = &[
-    Rect::new_from_x_y(0, 0, 60, 54),
-    Rect::new_from_x_y(60, 0, 384 - (60 * 2), 93),
-    Rect::new_from_x_y(384 - 60, 0, 60, 54),
-];
-
-//noinspection DuplicatedCode
-pub fn stone_and_platform(
-    stone: HtmlImageElement,
-    sprite_sheet: Rc<SpriteSheet>,
-    offset_x: i16,
-) -> Vec<Box<dyn Obstacle>> {
-    const INITIAL_STONE_OFFSET: i16 = 150;
-    vec![
-        Box::new(Barrier::new(Image::new(
-            stone,
-            Point {
-                x: offset_x + INITIAL_STONE_OFFSET,
-                y: STONE_ON_GROUND,
-            },
-        ))),
-        Box::new(create_floating_platform(
-            sprite_sheet,
-            Point {
-                x: offset_x + FIRST_PLATFORM,
-                y: LOW_PLATFORM,
-            },
-        )),
-    ]
+#[derive(Deserialize)]
+pub struct SegmentLibrary {
+    segments: Vec<SegmentDefinition>,
 }
 
-//noinspection DuplicatedCode
-pub fn platform_and_stone(
-    stone: HtmlImageElement,
-    sprite_sheet: Rc<SpriteSheet>,
-    offset_x: i16,
+#[derive(Deserialize)]
+pub struct SegmentDefinition {
+    pub name: String,
+    pub obstacles: Vec<ObstacleDefinition>,
+}
+
+/// Positions are relative to the start of the segment, and platform bounding
+/// boxes are relative to the platform's position.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ObstacleDefinition {
+    Barrier {
+        image: String,
+        x: i16,
+        y: i16,
+    },
+    Platform {
+        sprites: Vec<String>,
+        bounding_boxes: Vec<SheetRect>,
+        x: i16,
+        y: i16,
+        #[serde(default)]
+        behavior: PlatformBehavior,
+    },
+    /// `count` pickups in a line, `spacing` apart, such as along the top of
+    /// a platform.
+    CollectibleRow {
+        pickup: Pickup,
+        count: u8,
+        spacing: i16,
+        x: i16,
+        y: i16,
+    },
+    /// `count` pickups along a jump arc that starts and ends at `y`, is
+    /// `width` wide and peaks `height` above `y`.
+    CollectibleArc {
+        pickup: Pickup,
+        count: u8,
+        width: i16,
+        height: i16,
+        x: i16,
+        y: i16,
+    },
+    Enemy {
+        behavior: EnemyBehavior,
+        x: i16,
+        y: i16,
+    },
+    /// A gap in the ground `width` wide.
+    Pit {
+        x: i16,
+        width: i16,
+    },
+}
+
+/// Everything the obstacles in a segment are drawn with.
+pub struct SegmentAssets {
+    pub images: HashMap<String, HtmlImageElement>,
+    pub tiles: Rc<SpriteSheet>,
+    pub pickups: Rc<SpriteSheet>,
+    pub enemies: Rc<SpriteSheet>,
+}
+
+impl SegmentLibrary {
+    pub fn validate(self) -> Result<Self> {
+        let mut names = HashSet::new();
+        if let Some(duplicate) = self
+            .segments
+            .iter()
+            .find(|segment| !names.insert(segment.name.as_str()))
+        {
+            return Err(anyhow!("Segment {} is defined twice", duplicate.name));
+        }
+        if let Some(empty) = self
+            .segments
+            .iter()
+            .find(|segment| segment.obstacles.is_empty())
+        {
+            return Err(anyhow!("Segment {} has no obstacles", empty.name));
+        }
+        if let Some(segment) = self.segments.iter().find(|segment| {
+            segment.obstacles.iter().any(|obstacle| {
+                matches!(obstacle, ObstacleDefinition::Platform { sprites, bounding_boxes, .. }
+                    if sprites.is_empty() || bounding_boxes.is_empty())
+            })
+        }) {
+            return Err(anyhow!(
+                "Segment {} has a platform without sprites or bounding boxes",
+                segment.name
+            ));
+        }
+        if let Some(segment) = self.segments.iter().find(|segment| {
+            segment.obstacles.iter().any(
+                |obstacle| matches!(obstacle, ObstacleDefinition::Pit { width, .. } if *width <= 0),
+            )
+        }) {
+            return Err(anyhow!("Segment {} has a pit with no width", segment.name));
+        }
+        if let Some(segment) = self.segments.iter().find(|segment| {
+            segment.obstacles.iter().any(|obstacle| {
+                matches!(
+                    obstacle,
+                    ObstacleDefinition::CollectibleRow { count: 0, .. }
+                        | ObstacleDefinition::CollectibleArc { count: 0, .. }
+                )
+            })
+        }) {
+            return Err(anyhow!(
+                "Segment {} places an empty row of pickups",
+                segment.name
+            ));
+        }
+
+        Ok(self)
+    }
+
+    pub fn segment(&self, name: &str) -> Option<&SegmentDefinition> {
+        self.segments.iter().find(|segment| segment.name == name)
+    }
+
+    pub fn segments(&self) -> &[SegmentDefinition] {
+        &self.segments
+    }
+
+    /// The barrier images every segment needs, so they can be loaded up
+    /// front.
+    pub fn image_names(&self) -> HashSet<&str> {
+        self.segments
+            .iter()
+            .flat_map(|segment| segment.obstacles.iter())
+            .filter_map(|obstacle| match obstacle {
+                ObstacleDefinition::Barrier { image, .. } => Some(image.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn ensure_segments_exist(&self, difficulty: &Difficulty) -> Result<()> {
+        difficulty
+            .stages()
+            .iter()
+            .flat_map(|stage| stage.segments.iter())
+            .try_for_each(|name| {
+                self.segment(name)
+                    .map(|_segment| ())
+                    .ok_or_else(|| anyhow!("Difficulty uses unknown segment {}", name))
+            })
+    }
+
+    pub fn ensure_sprites_exist(
+        &self,
+        tiles: &SpriteSheet,
+        pickups: &SpriteSheet,
+        enemies: &SpriteSheet,
+    ) -> Result<()> {
+        let obstacles = || {
+            self.segments
+                .iter()
+                .flat_map(|segment| segment.obstacles.iter())
+        };
+        obstacles()
+            .filter_map(|obstacle| match obstacle {
+                ObstacleDefinition::Platform { sprites, .. } => Some(sprites),
+                _ => None,
+            })
+            .flatten()
+            .map(String::as_str)
+            .chain(
+                obstacles()
+                    .any(|obstacle| matches!(obstacle, ObstacleDefinition::Pit { .. }))
+                    .then_some(PIT_SPRITE),
+            )
+            .try_for_each(|sprite| {
+                tiles
+                    .cell(sprite)
+                    .map(|_cell| ())
+                    .ok_or_else(|| anyhow!("Segment sprite {} is not in the sprite sheet", sprite))
+            })?;
+        obstacles()
+            .filter_map(ObstacleDefinition::pickup)
+            .try_for_each(|pickup| {
+                pickups
+                    .cell(pickup.sprite_name())
+                    .map(|_cell| ())
+                    .ok_or_else(|| anyhow!("Pickup {:?} is not in the pickup sheet", pickup))
+            })?;
+        obstacles()
+            .filter_map(|obstacle| match obstacle {
+                ObstacleDefinition::Enemy { behavior, .. } => Some(behavior),
+                _ => None,
+            })
+            .flat_map(|behavior| {
+                std::iter::once(behavior.sprite_name()).chain(behavior.projectile_sprite_name())
+            })
+            .try_for_each(|sprite| {
+                enemies
+                    .cell(sprite)
+                    .map(|_cell| ())
+                    .ok_or_else(|| anyhow!("Enemy sprite {} is not in the enemy sheet", sprite))
+            })
+    }
+}
+
+impl SegmentDefinition {
+    pub fn build(&self, assets: &SegmentAssets, offset_x: i16) -> Vec<Box<dyn Obstacle>> {
+        self.obstacles
+            .iter()
+            .flat_map(|obstacle| -> Vec<Box<dyn Obstacle>> {
+                match obstacle {
+                    ObstacleDefinition::Barrier { image, x, y } => {
+                        let Some(element) = assets.images.get(image) else {
+                            error!("Barrier image {} was not loaded", image);
+                            return vec![];
+                        };
+                        vec![Box::new(Barrier::new(Image::new(
+                            element.clone(),
+                            Point {
+                                x: offset_x + x,
+                                y: *y,
+                            },
+                        )))]
+                    }
+                    ObstacleDefinition::Platform {
+                        sprites,
+                        bounding_boxes,
+                        x,
+                        y,
+                        behavior,
+                    } => vec![Box::new(create_platform(
+                        assets.tiles.clone(),
+                        Point {
+                            x: offset_x + x,
+                            y: *y,
+                        },
+                        sprites,
+                        bounding_boxes,
+                        *behavior,
+                    ))],
+                    ObstacleDefinition::CollectibleRow { .. }
+                    | ObstacleDefinition::CollectibleArc { .. } => vec![],
+                    ObstacleDefinition::Pit { x, width } => {
+                        create_pit(assets.tiles.clone(), offset_x + x, *width)
+                    }
+                    ObstacleDefinition::Enemy { behavior, x, y } => create_enemy(
+                        assets.enemies.clone(),
+                        *behavior,
+                        Point {
+                            x: offset_x + x,
+                            y: *y,
+                        },
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Pickups aren't obstacles, so `build` leaves them for the game to place.
+    pub fn pickups(&self, offset_x: i16) -> Vec<(Pickup, Point)> {
+        self.obstacles
+            .iter()
+            .filter_map(|obstacle| Some((obstacle.pickup()?, obstacle.pickup_positions())))
+            .flat_map(|(pickup, positions)| {
+                positions.into_iter().map(move |position| {
+                    (
+                        pickup,
+                        Point {
+                            x: offset_x + position.x,
+                            y: position.y,
+                        },
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+impl ObstacleDefinition {
+    fn pickup(&self) -> Option<Pickup> {
+        match self {
+            ObstacleDefinition::CollectibleRow { pickup, .. }
+            | ObstacleDefinition::CollectibleArc { pickup, .. } => Some(*pickup),
+            _ => None,
+        }
+    }
+
+    /// Where each pickup goes, relative to the start of the segment.
+    fn pickup_positions(&self) -> Vec<Point> {
+        match self {
+            ObstacleDefinition::CollectibleRow {
+                count,
+                spacing,
+                x,
+                y,
+                ..
+            } => (0..*count)
+                .map(|index| Point {
+                    x: x + index as i16 * spacing,
+                    y: *y,
+                })
+                .collect(),
+            ObstacleDefinition::CollectibleArc {
+                count,
+                width,
+                height,
+                x,
+                y,
+                ..
+            } => (0..*count)
+                .map(|index| {
+                    let t = if *count > 1 {
+                        index as f32 / (*count - 1) as f32
+                    } else {
+                        0.5
+                    };
+                    Point {
+                        x: x + (t * *width as f32).round() as i16,
+                        y: y - (4.0 * *height as f32 * t * (1.0 - t)).round() as i16,
+                    }
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+fn create_pit(sheet: Rc<SpriteSheet>, x: i16, width: i16) -> Vec<Box<dyn Obstacle>> {
+    let Some(water) = sheet.cell(PIT_SPRITE).cloned() else {
+        error!("Pit sprite {} not found", PIT_SPRITE);
+        return vec![];
+    };
+    vec![Box::new(Pit::new(sheet, water, x, width))]
+}
+
+fn create_enemy(
+    sheet: Rc<SpriteSheet>,
+    behavior: EnemyBehavior,
+    position: Point,
 ) -> Vec<Box<dyn Obstacle>> {
-    const INITIAL_STONE_OFFSET: i16 = 150;
-    vec![
-        Box::new(Barrier::new(Image::new(
-            stone,
-            Point {
-                x: offset_x + FIRST_PLATFORM,
-                y: STONE_ON_GROUND,
-            },
-        ))),
-        Box::new(create_floating_platform(
-            sprite_sheet,
-            Point {
-                x: offset_x + INITIAL_STONE_OFFSET,
-                y: HIGH_PLATFORM,
-            },
-        )),
-    ]
+    let Some(sprite) = sheet.cell(behavior.sprite_name()).cloned() else {
+        error!("Enemy sprite {} not found", behavior.sprite_name());
+        return vec![];
+    };
+    let projectile = behavior
+        .projectile_sprite_name()
+        .and_then(|name| sheet.cell(name).cloned());
+    vec![Box::new(Enemy::new(
+        sheet, sprite, projectile, behavior, position,
+    ))]
 }
 
-fn create_floating_platform(sprite_sheet: Rc<SpriteSheet>, position: Point) -> Platform {
+fn create_platform(
+    sprite_sheet: Rc<SpriteSheet>,
+    position: Point,
+    sprites: &[String],
+    bounding_boxes: &[SheetRect],
+    behavior: PlatformBehavior,
+) -> Platform {
+    let sprite_names: Vec<&str> = sprites.iter().map(String::as_str).collect();
+    let bounding_boxes: Vec<Rect> = bounding_boxes
+        .iter()
+        .map(|bounding_box| {
+            Rect::new_from_x_y(
+                bounding_box.x,
+                bounding_box.y,
+                bounding_box.w,
+                bounding_box.h,
+            )
+        })
+        .collect();
+
     Platform::new(
         sprite_sheet,
         position,
-        FLOATING_PLATFORM_SPRITES,
-        FLOATING_PLATFORM_BOUNDING_BOXES,
+        &sprite_names,
+        &bounding_boxes,
+        behavior,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Sheet;
+
+    fn bundled_segments() -> SegmentLibrary {
+        serde_json::from_str(include_str!("../static/segments.json")).unwrap()
+    }
+
+    fn library(json: &str) -> Result<SegmentLibrary> {
+        serde_json::from_str::<SegmentLibrary>(json)?.validate()
+    }
+
+    #[test]
+    fn bundled_segments_are_valid() {
+        assert!(bundled_segments().validate().is_ok());
+    }
+
+    #[test]
+    fn bundled_difficulty_only_uses_known_segments() {
+        let difficulty: Difficulty =
+            serde_json::from_str(include_str!("../static/difficulty.json")).unwrap();
+
+        assert!(bundled_segments()
+            .ensure_segments_exist(&difficulty)
+            .is_ok());
+    }
+
+    #[test]
+    fn bundled_segments_only_use_known_pickups() {
+        let sheet: Sheet = serde_json::from_str(include_str!("../static/pickups.json")).unwrap();
+
+        bundled_segments()
+            .segments()
+            .iter()
+            .flat_map(|segment| segment.obstacles.iter())
+            .filter_map(ObstacleDefinition::pickup)
+            .for_each(|pickup| {
+                assert!(
+                    sheet.frames.contains_key(pickup.sprite_name()),
+                    "missing {:?}",
+                    pickup
+                )
+            });
+    }
+
+    #[test]
+    fn bundled_segments_only_use_known_enemies() {
+        let sheet: Sheet = serde_json::from_str(include_str!("../static/enemies.json")).unwrap();
+
+        bundled_segments()
+            .segments()
+            .iter()
+            .flat_map(|segment| segment.obstacles.iter())
+            .for_each(|obstacle| {
+                if let ObstacleDefinition::Enemy { behavior, .. } = obstacle {
+                    std::iter::once(behavior.sprite_name())
+                        .chain(behavior.projectile_sprite_name())
+                        .for_each(|sprite| {
+                            assert!(sheet.frames.contains_key(sprite), "missing {}", sprite)
+                        });
+                }
+            });
+    }
+
+    #[test]
+    fn bundled_segments_only_use_known_tiles() {
+        let sheet: Sheet = serde_json::from_str(include_str!("../static/tiles.json")).unwrap();
+
+        bundled_segments()
+            .segments()
+            .iter()
+            .flat_map(|segment| segment.obstacles.iter())
+            .for_each(|obstacle| {
+                if let ObstacleDefinition::Platform { sprites, .. } = obstacle {
+                    sprites.iter().for_each(|sprite| {
+                        assert!(sheet.frames.contains_key(sprite), "missing {}", sprite)
+                    });
+                }
+            });
+    }
+
+    #[test]
+    fn unknown_segments_are_reported() {
+        let difficulty: Difficulty = serde_json::from_str(
+            r#"{"stages": [
+                {"from_meters": 0, "running_speed": 4, "segment_gap": 200, "segments": ["nope"]}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert!(bundled_segments()
+            .ensure_segments_exist(&difficulty)
+            .is_err());
+    }
+
+    #[test]
+    fn image_names_lists_each_barrier_image_once() {
+        let library = library(
+            r#"{"segments": [
+                {"name": "a", "obstacles": [
+                    {"type": "barrier", "image": "Stone.png", "x": 0, "y": 546},
+                    {"type": "barrier", "image": "Stone.png", "x": 200, "y": 546}
+                ]},
+                {"name": "b", "obstacles": [
+                    {"type": "barrier", "image": "Log.png", "x": 0, "y": 546}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            library.image_names(),
+            HashSet::from(["Stone.png", "Log.png"])
+        );
+    }
+
+    #[test]
+    fn duplicate_segment_names_are_rejected() {
+        let result = library(
+            r#"{"segments": [
+                {"name": "a", "obstacles": [{"type": "barrier", "image": "Stone.png", "x": 0, "y": 546}]},
+                {"name": "a", "obstacles": [{"type": "barrier", "image": "Stone.png", "x": 0, "y": 546}]}
+            ]}"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn platforms_need_bounding_boxes() {
+        let result = library(
+            r#"{"segments": [
+                {"name": "a", "obstacles": [
+                    {"type": "platform", "sprites": ["13.png"], "bounding_boxes": [], "x": 0, "y": 400}
+                ]}
+            ]}"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    fn positions(obstacle: &str) -> Vec<(i16, i16)> {
+        serde_json::from_str::<ObstacleDefinition>(obstacle)
+            .unwrap()
+            .pickup_positions()
+            .iter()
+            .map(|position| (position.x, position.y))
+            .collect()
+    }
+
+    #[test]
+    fn rows_space_pickups_evenly() {
+        let positions = positions(
+            r#"{"type": "collectible_row", "pickup": "coin", "count": 3, "spacing": 40, "x": 100, "y": 380}"#,
+        );
+
+        assert_eq!(positions, vec![(100, 380), (140, 380), (180, 380)]);
+    }
+
+    #[test]
+    fn arcs_rise_to_their_height_and_come_back_down() {
+        let positions = positions(
+            r#"{"type": "collectible_arc", "pickup": "gem", "count": 5, "width": 200, "height": 100, "x": 0, "y": 500}"#,
+        );
+
+        assert_eq!(
+            positions,
+            vec![(0, 500), (50, 425), (100, 400), (150, 425), (200, 500)]
+        );
+    }
+
+    #[test]
+    fn a_single_pickup_on_an_arc_sits_at_the_top() {
+        let positions = positions(
+            r#"{"type": "collectible_arc", "pickup": "heart", "count": 1, "width": 200, "height": 100, "x": 0, "y": 500}"#,
+        );
+
+        assert_eq!(positions, vec![(100, 400)]);
+    }
+
+    #[test]
+    fn segment_pickups_are_placed_after_the_offset() {
+        let segment: SegmentDefinition = serde_json::from_str(
+            r#"{"name": "a", "obstacles": [
+                {"type": "collectible_row", "pickup": "coin", "count": 2, "spacing": 40, "x": 100, "y": 380},
+                {"type": "pit", "x": 0, "width": 100},
+                {"type": "collectible_arc", "pickup": "heart", "count": 1, "width": 200, "height": 100, "x": 0, "y": 500}
+            ]}"#,
+        )
+        .unwrap();
+
+        let pickups: Vec<(Pickup, i16, i16)> = segment
+            .pickups(1000)
+            .into_iter()
+            .map(|(pickup, position)| (pickup, position.x, position.y))
+            .collect();
+
+        assert_eq!(
+            pickups,
+            vec![
+                (Pickup::Coin, 1100, 380),
+                (Pickup::Coin, 1140, 380),
+                (Pickup::Heart, 1100, 400)
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_rows_are_rejected() {
+        let result = library(
+            r#"{"segments": [
+                {"name": "a", "obstacles": [
+                    {"type": "collectible_row", "pickup": "coin", "count": 0, "spacing": 40, "x": 0, "y": 400}
+                ]}
+            ]}"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    fn platform_behavior(obstacle: &str) -> PlatformBehavior {
+        match serde_json::from_str::<ObstacleDefinition>(obstacle).unwrap() {
+            ObstacleDefinition::Platform { behavior, .. } => behavior,
+            _ => panic!("not a platform"),
+        }
+    }
+
+    #[test]
+    fn platforms_are_static_unless_told_otherwise() {
+        let behavior = platform_behavior(
+            r#"{"type": "platform", "sprites": ["13.png"], "bounding_boxes": [{"x": 0, "y": 0, "w": 128, "h": 93}], "x": 0, "y": 400}"#,
+        );
+
+        assert_eq!(behavior, PlatformBehavior::Static);
+    }
+
+    #[test]
+    fn platform_behaviors_are_read_from_segment_data() {
+        let behavior = platform_behavior(
+            r#"{"type": "platform", "sprites": ["13.png"], "bounding_boxes": [{"x": 0, "y": 0, "w": 128, "h": 93}], "x": 0, "y": 400,
+                "behavior": {"type": "oscillating", "amplitude": 30, "period": 120}}"#,
+        );
+
+        assert_eq!(
+            behavior,
+            PlatformBehavior::Oscillating {
+                amplitude: 30,
+                period: 120
+            }
+        );
+        assert_eq!(
+            platform_behavior(
+                r#"{"type": "platform", "sprites": [], "bounding_boxes": [], "x": 0, "y": 400, "behavior": {"type": "one_way"}}"#,
+            ),
+            PlatformBehavior::OneWay
+        );
+    }
+
+    #[test]
+    fn enemies_are_read_from_segment_data() {
+        let enemy = serde_json::from_str::<ObstacleDefinition>(
+            r#"{"type": "enemy", "behavior": {"type": "thrower", "interval": 90, "speed": 6}, "x": 300, "y": 532}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            enemy,
+            ObstacleDefinition::Enemy {
+                behavior: EnemyBehavior::Thrower {
+                    interval: 90,
+                    speed: 6
+                },
+                x: 300,
+                y: 532
+            }
+        ));
+    }
+
+    #[test]
+    fn pits_need_a_width() {
+        let result = library(
+            r#"{"segments": [
+                {"name": "a", "obstacles": [{"type": "pit", "x": 100, "width": 0}]}
+            ]}"#,
+        );
+
+        assert!(result.is_err());
+    }
+}
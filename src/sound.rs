@@ -3,10 +3,15 @@ use js_sys::ArrayBuffer;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-    AudioBuffer, AudioBufferSourceNode, AudioContext, AudioNode, GainNode,
-    GainOptions,
+    AudioBuffer, AudioBufferSourceNode, AudioContext, AudioNode, GainNode, GainOptions,
+    StereoPannerNode, StereoPannerOptions,
 };
 
+const CENTER: f32 = 0.0;
+// Keeps emitters at the very edge of the canvas from sounding like they only
+// come out of one speaker.
+const MAX_PAN: f32 = 0.8;
+
 pub fn create_audio_context() -> Result<AudioContext> {
     AudioContext::new().map_err(|e| anyhow!("Could not create audio context: {:#?}", e))
 }
@@ -25,6 +30,13 @@ fn create_gain_node(ctx: &AudioContext, gain: f32) -> Result<GainNode> {
     Ok(gain_node)
 }
 
+fn create_stereo_panner_node(ctx: &AudioContext, pan: f32) -> Result<StereoPannerNode> {
+    let panner_options = StereoPannerOptions::new();
+    panner_options.set_pan(pan);
+    StereoPannerNode::new_with_options(ctx, &panner_options)
+        .map_err(|e| anyhow!("Error creating stereo panner {:#?}", e))
+}
+
 fn connect_audio_nodes(source: &AudioNode, destination: &AudioNode) -> Result<AudioNode> {
     source
         .connect_with_audio_node(destination)
@@ -35,12 +47,14 @@ fn create_track_source(
     ctx: &AudioContext,
     buffer: &AudioBuffer,
     gain: f32,
+    pan: f32,
 ) -> Result<AudioBufferSourceNode> {
     let track_source = create_buffer_source(ctx)?;
     track_source.set_buffer(Some(buffer));
     let gain = create_gain_node(ctx, gain)?;
+    let panner = create_stereo_panner_node(ctx, pan)?;
     connect_audio_nodes(
-        &connect_audio_nodes(&track_source, &gain)?,
+        &connect_audio_nodes(&connect_audio_nodes(&track_source, &gain)?, &panner)?,
         &ctx.destination(),
     )?;
 
@@ -61,14 +75,35 @@ pub async fn decode_audio_data(
     .map_err(|e| anyhow!("Could not cast into AudioBuffer {:#?}", e))
 }
 
-pub enum LOOPING {
-    NO,
-    YES,
+pub enum Looping {
+    No,
+    Yes,
 }
 
-pub fn play_sound(ctx: &AudioContext, buffer: &AudioBuffer, looping: LOOPING) -> Result<()> {
-    let track_source = create_track_source(ctx, buffer, 0.5)?;
-    if matches!(looping, LOOPING::YES) {
+/// Maps an emitter's x position onto the [-1.0, 1.0] range a StereoPannerNode
+/// expects: the left edge of the canvas pans left, the right edge pans right
+/// and anything off-screen is clamped to the nearest edge.
+pub fn pan_for_position(x: i16, canvas_width: i16) -> f32 {
+    if canvas_width <= 0 {
+        return CENTER;
+    }
+    let relative = (x as f32 / canvas_width as f32).clamp(0.0, 1.0);
+
+    (relative * 2.0 - 1.0) * MAX_PAN
+}
+
+pub fn play_sound(ctx: &AudioContext, buffer: &AudioBuffer, looping: Looping) -> Result<()> {
+    play_panned_sound(ctx, buffer, looping, CENTER)
+}
+
+pub fn play_panned_sound(
+    ctx: &AudioContext,
+    buffer: &AudioBuffer,
+    looping: Looping,
+    pan: f32,
+) -> Result<()> {
+    let track_source = create_track_source(ctx, buffer, 0.5, pan)?;
+    if matches!(looping, Looping::Yes) {
         track_source.set_loop(true)
     }
 
@@ -76,3 +111,35 @@ pub fn play_sound(ctx: &AudioContext, buffer: &AudioBuffer, looping: LOOPING) ->
         .start()
         .map_err(|e| anyhow!("Could not start sound!{:#?}", e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emitter_in_the_middle_is_centered() {
+        assert_eq!(pan_for_position(300, 600), 0.0);
+    }
+
+    #[test]
+    fn emitters_at_the_edges_pan_to_the_limit() {
+        assert_eq!(pan_for_position(0, 600), -MAX_PAN);
+        assert_eq!(pan_for_position(600, 600), MAX_PAN);
+    }
+
+    #[test]
+    fn emitters_off_screen_are_clamped() {
+        assert_eq!(pan_for_position(-200, 600), -MAX_PAN);
+        assert_eq!(pan_for_position(1200, 600), MAX_PAN);
+    }
+
+    #[test]
+    fn pan_increases_from_left_to_right() {
+        let left = pan_for_position(150, 600);
+        let right = pan_for_position(450, 600);
+
+        assert!(left < 0.0);
+        assert!(right > 0.0);
+        assert!(left < right);
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::eq_op)]
+
 use wasm_bindgen_test::{wasm_bindgen_test_configure, wasm_bindgen_test};
 
 wasm_bindgen_test_configure!(run_in_browser);
@@ -11,6 +13,7 @@ fn rust_test() {
 
 
 // This runs a unit test in the browser, so it can use browser APIs.
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
 #[wasm_bindgen_test]
 fn web_test() {
     assert_eq!(1, 1);